@@ -1,20 +1,36 @@
-use std::time::Duration;
+use std::{f64::consts::TAU, ops::Range, path::Path, time::Duration};
 
-use egui::plot::{PlotPoint, PlotPoints};
+use egui::plot::PlotPoint;
 
+use egui::{
+    text::{CCursor, CCursorRange},
+    text_edit::TextEditState,
+    TextStyle,
+};
 use evalexpr::EvalexprError;
-use rand::Rng;
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha8Rng;
 
 use crate::{
-    eval::MathExpression,
-    plotter::{compute_line_points, compute_polygon_points, get_app_plot, Plotter},
-    ui::{rich_text, Message, UITypes},
+    editor::{EditorTool, LevelEditor},
+    eval::{
+        ExpressionError, MathExpression, FIRST_ORDER_ODE_VARIABLES, PARAMETRIC_VARIABLES,
+        POLAR_VARIABLES, SECOND_ORDER_ODE_VARIABLES, UNICODE_SYMBOLS,
+    },
+    level::{Level, LevelObstacle, LevelRules, OBSTACLE_SIDES},
+    plotter::{
+        compute_line_points, compute_ode_points, compute_parametric_points, compute_polar_points,
+        compute_polygon_points, distance, get_app_plot, BoardConfig, DiscontinuityRule,
+        LineSampling, OdeOrder, Plotter, ShotDirection, Trajectory, ENTITY_AMPLITUDE,
+    },
+    ui::{highlighted_layout_job, render_functions_help, rich_text, Message, UITypes},
 };
 
 struct EntitiesPos {
     player: (Vec<PlotPoint>, PlotPoint),       // (sprite, position)
     enemies: Vec<(Vec<PlotPoint>, PlotPoint)>, // Vec<(sprite, position)>
     obstacles: Vec<(Vec<PlotPoint>, PlotPoint, f64)>, // Vec<(sprite, position, amplitude)>
+    placement_issues: Vec<String>,             // the constraints that couldn't be met
 }
 
 #[derive(PartialEq, Debug)]
@@ -26,204 +42,789 @@ enum CollisionType {
 #[derive(PartialEq, Debug)]
 struct Collision {
     entity_point: PlotPoint,
-    frame_id: usize,
+    hit_point: PlotPoint, // where the trajectory enters the entity
+    frame_id: usize,      // the trajectory point just before the hit
+    position: f64,        // frame_id plus how far the hit is toward the next point, in [0, 1]
     collision_type: CollisionType,
     entity_id: usize,
 }
 
-#[allow(non_snake_case)]
-fn distance_bewteen_two_points(A: &PlotPoint, B: &PlotPoint) -> f64 {
-    ((B.x - A.x).powi(2) + (B.y - A.y).powi(2)).sqrt()
+/// how the equation typed by the player becomes a trajectory
+#[derive(PartialEq, Clone, Copy)]
+enum ShotMode {
+    Function,       // y = f(x)
+    FirstOrderOde,  // y' = f(x, y)
+    SecondOrderOde, // y'' = f(x, y, y')
+    Parametric,     // (x(t), y(t))
+    Polar,          // r = f(θ)
+}
+
+impl ShotMode {
+    const ALL: [ShotMode; 5] = [
+        ShotMode::Function,
+        ShotMode::FirstOrderOde,
+        ShotMode::SecondOrderOde,
+        ShotMode::Parametric,
+        ShotMode::Polar,
+    ];
+
+    fn name(&self) -> &'static str {
+        match self {
+            ShotMode::Function => "function",
+            ShotMode::FirstOrderOde => "1st order ODE",
+            ShotMode::SecondOrderOde => "2nd order ODE",
+            ShotMode::Parametric => "parametric",
+            ShotMode::Polar => "polar",
+        }
+    }
+
+    /// one label per equation the mode needs
+    fn equation_labels(&self) -> &'static [&'static str] {
+        match self {
+            ShotMode::Function => &["Line equation:"],
+            ShotMode::FirstOrderOde => &["Line equation: y' = f(x, y) ="],
+            ShotMode::SecondOrderOde => &["Line equation: y'' = f(x, y, y') ="],
+            ShotMode::Parametric => &["x(t) =", "y(t) ="],
+            ShotMode::Polar => &["r(θ) ="],
+        }
+    }
+}
+
+#[derive(Default)]
+struct EquationInput {
+    text: String,
+    error_span: Option<Range<usize>>, // chars of the equation that made the last shot fail
+}
+
+/// options of the settings window
+struct Settings {
+    anchored_shots: bool, // shift function shots by -f(0) so that they leave from the player
+    discontinuity_rule: DiscontinuityRule,
+    board: BoardConfig,
 }
 
-const ENTITY_AMPLITUDE: f64 = 1.0;
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            // the original game behavior
+            anchored_shots: true,
+            discontinuity_rule: DiscontinuityRule::Stop,
+            board: BoardConfig::default(),
+        }
+    }
+}
+
+fn translate_sprite(sprite: &[PlotPoint], pos: &PlotPoint) -> Vec<PlotPoint> {
+    sprite
+        .iter()
+        .map(|&PlotPoint { x, y }| PlotPoint::new(x + pos.x, y + pos.y))
+        .collect()
+}
+
+/// where a function shot can pass on the board: the board is cut in cells, and a cell is
+/// reachable when a curve y = f(x) going forward from the player can get there without
+/// touching obstacles or leaving the board
+struct ReachabilityGrid {
+    origin: PlotPoint,         // center of the bottom left cell
+    reachable: Vec<Vec<bool>>, // reachable[column][row]
+}
+
+impl ReachabilityGrid {
+    fn new(
+        board: &BoardConfig,
+        player_pos: &PlotPoint,
+        obstacles: &[(Vec<PlotPoint>, PlotPoint, f64)],
+    ) -> Self {
+        let ((x_min, _), (y_min, _)) = (board.x_bounds(), board.y_bounds());
+        let columns = (board.width / SOLVER_CELL_SIZE).floor() as usize;
+        let rows = (board.height / SOLVER_CELL_SIZE).floor() as usize;
+        let origin = PlotPoint::new(
+            x_min + SOLVER_CELL_SIZE / 2.0,
+            y_min + SOLVER_CELL_SIZE / 2.0,
+        );
+        let cell_center = |column: usize, row: usize| {
+            PlotPoint::new(
+                origin.x + column as f64 * SOLVER_CELL_SIZE,
+                origin.y + row as f64 * SOLVER_CELL_SIZE,
+            )
+        };
+        // a cell is free when no part of it is in an obstacle
+        let half_diagonal = SOLVER_CELL_SIZE * std::f64::consts::FRAC_1_SQRT_2;
+        let is_free = |column: usize, row: usize| {
+            let center = cell_center(column, row);
+            obstacles.iter().all(|(_, obstacle_pos, amplitude)| {
+                distance(&center, obstacle_pos) > amplitude + half_diagonal
+            })
+        };
+        // a curve can go up and down as much as it wants, but only within a run of free cells
+        let free_run = |column: usize, row: usize| {
+            let start = (0..=row)
+                .rev()
+                .take_while(|&row| is_free(column, row))
+                .last()
+                .unwrap_or(row);
+            let end = (row..rows).take_while(|&row| is_free(column, row)).last();
+            end.map(|end| start..=end)
+        };
+
+        let mut reachable = vec![vec![false; rows]; columns];
+        let to_cell = |value: f64, min: f64, cells: usize| {
+            (((value - min) / SOLVER_CELL_SIZE).floor().max(0.0) as usize).min(cells - 1)
+        };
+        let player_column = to_cell(player_pos.x, x_min, columns);
+        let player_row = to_cell(player_pos.y, y_min, rows);
+        let forward_columns: Vec<usize> = match ShotDirection::facing_from(player_pos) {
+            ShotDirection::Right => (player_column..columns).collect(),
+            ShotDirection::Left => (0..=player_column).rev().collect(),
+        };
+
+        let mut previous_column: Option<usize> = None;
+        for column in forward_columns {
+            let entry_rows: Vec<usize> = match previous_column {
+                None => vec![player_row], // the shot starts on the player
+                Some(previous) => (0..rows).filter(|&row| reachable[previous][row]).collect(),
+            };
+            for row in entry_rows {
+                if reachable[column][row] {
+                    continue; // already in a reached run
+                }
+                if let Some(run) = free_run(column, row) {
+                    for row in run {
+                        reachable[column][row] = true;
+                    }
+                }
+            }
+            previous_column = Some(column);
+        }
+
+        Self { origin, reachable }
+    }
+
+    fn reachable_cells(&self) -> impl Iterator<Item = PlotPoint> + '_ {
+        self.reachable
+            .iter()
+            .enumerate()
+            .flat_map(move |(column, rows)| {
+                rows.iter()
+                    .enumerate()
+                    .filter(|(_, is_reachable)| **is_reachable)
+                    .map(move |(row, _)| {
+                        PlotPoint::new(
+                            self.origin.x + column as f64 * SOLVER_CELL_SIZE,
+                            self.origin.y + row as f64 * SOLVER_CELL_SIZE,
+                        )
+                    })
+            })
+    }
+
+    /// whether a shot can touch the entity at `pos`
+    fn can_hit(&self, pos: &PlotPoint, amplitude: f64) -> bool {
+        self.reachable_cells()
+            .any(|cell| distance(&cell, pos) <= amplitude)
+    }
+}
+
+/// move the enemies that function shots can't hit to reachable places,
+/// returns false when some of them can't be moved
+fn make_enemies_reachable(entities: &mut EntitiesPos, board: &BoardConfig, seed: u64) -> bool {
+    let player_pos = entities.player.1;
+    let grid = ReachabilityGrid::new(board, &player_pos, &entities.obstacles);
+    let ((x_min, x_max), (y_min, y_max)) = (board.x_bounds(), board.y_bounds());
+    let spots: Vec<PlotPoint> = grid
+        .reachable_cells()
+        .filter(|spot| {
+            (x_min + board.margin..=x_max - board.margin).contains(&spot.x)
+                && (y_min + board.margin..=y_max - board.margin).contains(&spot.y)
+                && distance(&player_pos, spot) > MIN_ENEMY_DISTANCE
+        })
+        .collect();
+
+    let mut rng = ChaCha8Rng::seed_from_u64(seed);
+    let entity_sprite = compute_polygon_points(100, ENTITY_AMPLITUDE)
+        .points()
+        .to_vec();
+    for ennemy_id in 0..entities.enemies.len() {
+        if grid.can_hit(&entities.enemies[ennemy_id].1, ENTITY_AMPLITUDE) {
+            continue;
+        }
+        if spots.is_empty() {
+            return false;
+        }
+        let taken_points: Vec<(PlotPoint, f64)> = std::iter::once((player_pos, ENTITY_AMPLITUDE))
+            .chain(
+                entities
+                    .enemies
+                    .iter()
+                    .enumerate()
+                    .filter(|(id, _)| *id != ennemy_id)
+                    .map(|(_, (_, pos))| (*pos, ENTITY_AMPLITUDE)),
+            )
+            .chain(
+                entities
+                    .obstacles
+                    .iter()
+                    .map(|(_, pos, amplitude)| (*pos, *amplitude)),
+            )
+            .collect();
+        let candidate =
+            |rng: &mut ChaCha8Rng| (spots[rng.gen_range(0..spots.len())], ENTITY_AMPLITUDE);
+        match place_entity(&mut rng, &taken_points, candidate, |_| true) {
+            Some((pos, _)) => {
+                entities.enemies[ennemy_id] = (translate_sprite(&entity_sprite, &pos), pos)
+            }
+            None => return false,
+        }
+    }
+    true
+}
+
+/// how many random positions are tried for an entity before giving up on it
+const PLACEMENT_ATTEMPTS: usize = 200;
+
+/// try `candidate` positions (with their amplitude) until one doesn't overlap the taken points
+/// and is allowed, within the attempts budget
+fn place_entity(
+    rng: &mut ChaCha8Rng,
+    taken_points: &[(PlotPoint, f64)],
+    mut candidate: impl FnMut(&mut ChaCha8Rng) -> (PlotPoint, f64),
+    is_allowed: impl Fn(&PlotPoint) -> bool,
+) -> Option<(PlotPoint, f64)> {
+    let does_position_overlap = |(point_to_check, amplitude): &(PlotPoint, f64)| {
+        taken_points.iter().any(|(taken_point, taken_amplitude)| {
+            distance(point_to_check, taken_point) <= taken_amplitude + amplitude
+        })
+    };
+    (0..PLACEMENT_ATTEMPTS)
+        .map(|_| candidate(rng))
+        .find(|placed| !does_position_overlap(placed) && is_allowed(&placed.0))
+}
+
+/// the first position in [0, 1] along the segment [from, to] which is in the circle
+fn segment_circle_intersection(
+    from: &PlotPoint,
+    to: &PlotPoint,
+    center: &PlotPoint,
+    radius: f64,
+) -> Option<f64> {
+    let (dx, dy) = (to.x - from.x, to.y - from.y);
+    let (fx, fy) = (from.x - center.x, from.y - center.y);
+    // solve |from + t * (to - from) - center|² = radius² for t
+    let c = fx * fx + fy * fy - radius * radius;
+    if c <= 0.0 {
+        return Some(0.0); // the segment starts inside
+    }
+    let a = dx * dx + dy * dy;
+    let b = 2.0 * (fx * dx + fy * dy);
+    let discriminant = b * b - 4.0 * a * c;
+    if a == 0.0 || discriminant < 0.0 {
+        return None;
+    }
+    let t = (-b - discriminant.sqrt()) / (2.0 * a);
+    (0.0..=1.0).contains(&t).then_some(t)
+}
+
+/// the first position in [0, 1] along the segment [from, to] which is in the polygon
+fn segment_polygon_intersection(
+    from: &PlotPoint,
+    to: &PlotPoint,
+    polygon: &[PlotPoint],
+) -> Option<f64> {
+    if is_inside_polygon(from, polygon) {
+        return Some(0.0);
+    }
+    let edges = polygon.iter().zip(polygon.iter().cycle().skip(1));
+    edges
+        .filter_map(|(a, b)| segments_intersection(from, to, a, b))
+        .min_by(f64::total_cmp)
+}
+
+/// the position in [0, 1] along the segment [from, to] where it crosses the segment [a, b]
+fn segments_intersection(
+    from: &PlotPoint,
+    to: &PlotPoint,
+    a: &PlotPoint,
+    b: &PlotPoint,
+) -> Option<f64> {
+    let cross = |(x1, y1): (f64, f64), (x2, y2): (f64, f64)| x1 * y2 - y1 * x2;
+    let r = (to.x - from.x, to.y - from.y);
+    let s = (b.x - a.x, b.y - a.y);
+    let from_to_a = (a.x - from.x, a.y - from.y);
+    let denominator = cross(r, s);
+    if denominator == 0.0 {
+        return None; // parallel segments
+    }
+    let t = cross(from_to_a, s) / denominator;
+    let u = cross(from_to_a, r) / denominator;
+    ((0.0..=1.0).contains(&t) && (0.0..=1.0).contains(&u)).then_some(t)
+}
+
+/// ray casting: count how many edges a horizontal ray from the point crosses
+fn is_inside_polygon(point: &PlotPoint, polygon: &[PlotPoint]) -> bool {
+    let edges = polygon.iter().zip(polygon.iter().cycle().skip(1));
+    edges
+        .filter(|(a, b)| {
+            (a.y > point.y) != (b.y > point.y)
+                && point.x < a.x + (point.y - a.y) / (b.y - a.y) * (b.x - a.x)
+        })
+        .count()
+        % 2
+        == 1
+}
+
+/// where a trajectory leaves the board
+#[derive(Debug, PartialEq)]
+enum BoardExit {
+    Crossing(usize, PlotPoint), // (index of the point before the edge, point on the edge)
+    OffBoard(usize),            // a segment starts off the board at this point (or the shot does)
+}
+
+/// the first place where the trajectory leaves the board
+fn find_board_exit(trajectory: &Trajectory, board: &BoardConfig) -> Option<BoardExit> {
+    trajectory.links().find_map(|(frame_id, from, to)| {
+        if !board.contains(&from) {
+            return Some(BoardExit::OffBoard(frame_id));
+        }
+        if board.contains(&to) {
+            return None;
+        }
+        // how far along the segment each crossed side of the board is
+        let exit_fraction = |from: f64, to: f64, (min, max): (f64, f64)| match to {
+            to if to > max => (max - from) / (to - from),
+            to if to < min => (min - from) / (to - from),
+            _ => 1.0,
+        };
+        let fraction = exit_fraction(from.x, to.x, board.x_bounds()).min(exit_fraction(
+            from.y,
+            to.y,
+            board.y_bounds(),
+        ));
+        let exit_point = PlotPoint::new(
+            from.x + fraction * (to.x - from.x),
+            from.y + fraction * (to.y - from.y),
+        );
+        Some(BoardExit::Crossing(frame_id, exit_point))
+    })
+}
+
+const CLASSIC_BOARD_AREA: f64 = 50.0 * 50.0;
+const MIN_ENEMY_DISTANCE: f64 = 10.0; // from the player
+const SOLVER_CELL_SIZE: f64 = ENTITY_AMPLITUDE / 2.0;
+const SOLVER_ATTEMPTS: u64 = 10; // boards generated before giving up on a solvable one
+                                 // function shots are sampled so that they can't step over an entity
+const FUNCTION_SAMPLING: LineSampling = LineSampling {
+    step: ENTITY_AMPLITUDE / 4.0,
+    max_segment_length: ENTITY_AMPLITUDE / 4.0,
+    max_points: 20_000,
+    until_off_board: true,
+};
+// the derivative is only drawn, it goes on when it leaves the board
+const DERIVATIVE_SAMPLING: LineSampling = LineSampling {
+    until_off_board: false,
+    ..FUNCTION_SAMPLING
+};
+const ANIMATION_FRAMES: usize = 60; // a shot is drawn in about a second, whatever its points
+const MAX_PARAMETER: f64 = 50.0; // bound of the parametric and polar shots parameter
 
 pub struct GraphWar {
-    equation: String,
+    equations: [EquationInput; 2], // the second one is only used by parametric shots
+    palette_target: usize,         // equation in which the symbol palette inserts
+    shot_mode: ShotMode,
+    ode_initial_slope: f64,
+    shot_angle: f64, // in degrees, rotation of the function shots around the player
+    parametric_range: (f64, f64),
+    polar_range: (f64, f64),
 
-    graph_cached_points: Option<Vec<PlotPoint>>,
+    graph_cached_points: Option<Trajectory>,
+    graph_exit_point: Option<PlotPoint>, // where the shot left the board
+    derivative_cached: Option<(String, Trajectory)>, // (formula, trajectory)
+    anchor_offset: Option<f64>,          // f(0) of the last anchored shot
+    show_derivative: bool,
     graph_animation_frame: usize,
-    graph_animation_speed: usize,
+    graph_animation_speed: usize, // points drawn per frame
     graph_resolution: usize,
     enemies_killed: Vec<(PlotPoint, usize)>, // (enemy_pos, frame_id)
 
     player: (Vec<PlotPoint>, PlotPoint), // (sprite, position)
     enemies: Vec<(Vec<PlotPoint>, PlotPoint)>, // Vec<(sprite, position)>
     obstacles: Vec<(Vec<PlotPoint>, PlotPoint, f64)>, // Vec<(sprite, position, amplitude)>
+    board_seed: u64, // the same seed and board config always give the same entities
+    seed_input: String,
+    level_path_input: String,
+    editor: Option<LevelEditor>, // kept while test playing its level
+    editing: bool,
+    test_playing: bool, // the board comes from the editor
 
     messages: Vec<Message>,
+    show_functions_help: bool,
+    settings: Settings,
+    level_rules: LevelRules, // of the level being played, they prevail over the settings
+    show_settings: bool,
+    board_resizing: bool, // the board size is being dragged, the board is generated on release
 }
 
 impl Default for GraphWar {
     fn default() -> Self {
-        let EntitiesPos {
-            obstacles,
-            player,
-            enemies,
-        } = Self::compute_all_entities_position();
+        let settings = Settings::default();
+        let (
+            EntitiesPos {
+                obstacles,
+                player,
+                enemies,
+                placement_issues,
+            },
+            board_seed,
+        ) = Self::generate_solvable_entities(&settings.board, rand::random());
+        let mut messages = vec![Message::new(
+            "Your are the green thingy, your goal is to aim at the red thingies without touching the purpule thingies".to_string(),
+            Duration::from_secs(6),
+            UITypes::Info,
+        )];
+        for issue in placement_issues {
+            messages.insert(
+                0,
+                Message::new(issue, Duration::from_secs(6), UITypes::Warning),
+            );
+        }
 
         Self {
-            equation: String::new(),
+            equations: Default::default(),
+            palette_target: 0,
+            shot_mode: ShotMode::Function,
+            ode_initial_slope: 0.0,
+            shot_angle: 0.0,
+            parametric_range: (0.0, 10.0),
+            polar_range: (0.0, TAU),
 
             graph_resolution: 100,
             graph_cached_points: None,
+            graph_exit_point: None,
+            derivative_cached: None,
+            anchor_offset: None,
+            show_derivative: false,
             graph_animation_frame: 0,
-            graph_animation_speed: 85,
+            graph_animation_speed: 1,
             enemies_killed: vec![],
 
             player,
             enemies,
             obstacles,
+            board_seed,
+            seed_input: board_seed.to_string(),
+            level_path_input: String::new(),
+            editor: None,
+            editing: false,
+            test_playing: false,
 
-            messages: vec![Message::new(
-                "Your are the green thingy, your goal is to aim at the red thingies without touching the purpule thingies".to_string(),
-                Duration::from_secs(6),
-                UITypes::Info,
-            )],
+            messages,
+            show_functions_help: false,
+            settings,
+            level_rules: LevelRules::default(),
+            show_settings: false,
+            board_resizing: false,
         }
     }
 }
 
 impl GraphWar {
     fn new_game(&mut self) {
+        self.new_game_with_seed(rand::random());
+    }
+
+    fn new_game_with_seed(&mut self, seed: u64) {
+        let (
+            EntitiesPos {
+                obstacles,
+                player,
+                enemies,
+                placement_issues,
+            },
+            seed,
+        ) = Self::generate_solvable_entities(&self.settings.board, seed);
+        for issue in placement_issues {
+            self.messages.insert(
+                0,
+                Message::new(issue, Duration::from_secs(6), UITypes::Warning),
+            );
+        }
+        self.obstacles = obstacles;
+        self.player = player;
+        self.enemies = enemies;
+        self.board_seed = seed;
+        self.seed_input = seed.to_string();
+        self.test_playing = false;
+        self.level_rules = LevelRules::default();
+        self.enemies_killed = vec![];
+        self.hide_graph();
+        self.graph_animation_frame = 0;
+    }
+
+    /// replace the board by the level at `path`, or tell why it can't be played
+    pub fn load_level_file(&mut self, path: &str) {
+        self.level_path_input = path.to_string();
+        match Level::load(Path::new(path)) {
+            Ok(level) => {
+                self.play_level(&level);
+                self.test_playing = false;
+                self.messages.insert(
+                    0,
+                    Message::new(
+                        format!("level {path} loaded"),
+                        Duration::from_secs(4),
+                        UITypes::Success,
+                    ),
+                );
+            }
+            Err(why) => self.messages.insert(
+                0,
+                Message::new(why.to_string(), Duration::from_secs(8), UITypes::Error),
+            ),
+        }
+    }
+
+    /// replace the board by the one of a valid level, and play it with its rules
+    fn play_level(&mut self, level: &Level) {
+        self.editing = false;
+        self.settings.board = level.board;
+        self.level_rules = level.rules.clone();
         let EntitiesPos {
             obstacles,
             player,
             enemies,
-        } = Self::compute_all_entities_position();
+            ..
+        } = Self::level_entities(level);
         self.obstacles = obstacles;
         self.player = player;
         self.enemies = enemies;
         self.enemies_killed = vec![];
-        self.graph_cached_points = None;
+        self.hide_graph();
         self.graph_animation_frame = 0;
     }
 
-    fn compute_all_entities_position() -> EntitiesPos {
-        let mut taken_points: Vec<(PlotPoint, f64)> = vec![];
-        let does_position_overlap =
-            |taken_points: &Vec<(PlotPoint, f64)>, point_to_check: (&PlotPoint, f64)| {
-                for taken_point in taken_points {
-                    let distance = distance_bewteen_two_points(point_to_check.0, &taken_point.0);
-                    if distance <= taken_point.1 + point_to_check.1 {
-                        return true;
-                    }
-                }
-                false
-            };
-
-        let mut rng = rand::thread_rng();
-        let obstacles_nums = rng.gen_range(5..=15);
-
-        let obstacles_sprites = (0..obstacles_nums)
-            .map(|_| {
-                let mut obstacle_pos = Self::spawn_entity();
-                let mut amplitude = rng.gen_range(2..=6) as f64;
-                while does_position_overlap(&taken_points, (&obstacle_pos, amplitude)) {
-                    obstacle_pos = Self::spawn_entity();
-                    amplitude = rng.gen_range(2..=6) as f64;
-                }
-                taken_points.push((obstacle_pos, amplitude));
-
-                // rng.gen_range(3..=15)
-                let obstacle_sprite = compute_polygon_points(20, amplitude).points().to_vec();
+    /// the current board, as a level. only the rules of the level being played are kept,
+    /// the player's settings are not written in it
+    fn current_level(&self) -> Level {
+        let position = |pos: &PlotPoint| [pos.x, pos.y];
+        Level {
+            board: self.settings.board,
+            player: position(&self.player.1),
+            enemies: self.enemies.iter().map(|(_, pos)| position(pos)).collect(),
+            obstacles: self
+                .obstacles
+                .iter()
+                .map(|(sprite, pos, amplitude)| LevelObstacle {
+                    position: position(pos),
+                    radius: *amplitude,
+                    sides: sprite.len(), // the sprite has a point per side
+                })
+                .collect(),
+            rules: self.level_rules.clone(),
+        }
+    }
 
-                (
-                    obstacle_sprite
-                        .iter()
-                        .map(|&PlotPoint { x, y }| [x + obstacle_pos.x, y + obstacle_pos.y])
-                        .collect::<PlotPoints>()
-                        .points()
-                        .to_vec(),
-                    obstacle_pos,
-                    amplitude,
-                )
-            })
-            .collect::<Vec<_>>();
+    /// open the editor, on the level being edited if any, or on the current board
+    fn open_editor(&mut self) {
+        if self.editor.is_none() || !self.test_playing {
+            self.editor = Some(LevelEditor::new(
+                self.current_level(),
+                self.level_path_input.clone(),
+            ));
+        }
+        self.editing = true;
+        self.test_playing = false;
+        self.hide_graph();
+    }
 
+    /// the entities of a level, which has been validated
+    fn level_entities(level: &Level) -> EntitiesPos {
         let entity_sprite = compute_polygon_points(100, ENTITY_AMPLITUDE)
             .points()
             .to_vec();
+        let point = |[x, y]: [f64; 2]| PlotPoint::new(x, y);
+        let player_pos = point(level.player);
 
-        let mut player_pos = Self::spawn_entity();
-        while does_position_overlap(&taken_points, (&player_pos, ENTITY_AMPLITUDE)) {
-            player_pos = Self::spawn_entity();
+        EntitiesPos {
+            player: (translate_sprite(&entity_sprite, &player_pos), player_pos),
+            enemies: level
+                .enemies
+                .iter()
+                .map(|&pos| (translate_sprite(&entity_sprite, &point(pos)), point(pos)))
+                .collect(),
+            obstacles: level
+                .obstacles
+                .iter()
+                .map(|obstacle| {
+                    let pos = point(obstacle.position);
+                    let obstacle_sprite = compute_polygon_points(obstacle.sides, obstacle.radius)
+                        .points()
+                        .to_vec();
+                    (
+                        translate_sprite(&obstacle_sprite, &pos),
+                        pos,
+                        obstacle.radius,
+                    )
+                })
+                .collect(),
+            placement_issues: vec![],
         }
-        taken_points.push((player_pos, ENTITY_AMPLITUDE));
+    }
 
-        let player_sprite = entity_sprite
-            .iter()
-            .map(|&PlotPoint { x, y }| [x + player_pos.x, y + player_pos.y])
-            .collect::<PlotPoints>()
+    /// generate the entities from `seed`, or from the next seeds when some enemies can't be made
+    /// reachable. the seed of the returned entities is returned along, so that they can be replayed
+    fn generate_solvable_entities(board: &BoardConfig, seed: u64) -> (EntitiesPos, u64) {
+        let mut tried_seed = seed;
+        for _ in 0..SOLVER_ATTEMPTS {
+            let mut entities = Self::compute_all_entities_position(board, tried_seed);
+            if make_enemies_reachable(&mut entities, board, tried_seed) {
+                return (entities, tried_seed);
+            }
+            tried_seed = tried_seed.wrapping_add(1);
+        }
+
+        let mut entities = Self::compute_all_entities_position(board, seed);
+        entities
+            .placement_issues
+            .push("some enemies may be out of reach on this board".to_string());
+        (entities, seed)
+    }
+
+    /// place the player, then the enemies and finally the obstacles, each within a budget of
+    /// attempts. entities that can't fit are left out (or placed with a relaxed rule for the
+    /// first enemy), which is reported in `placement_issues`
+    fn compute_all_entities_position(board: &BoardConfig, seed: u64) -> EntitiesPos {
+        let mut rng = ChaCha8Rng::seed_from_u64(seed);
+        let mut taken_points: Vec<(PlotPoint, f64)> = vec![];
+        let mut placement_issues = vec![];
+        let entity_sprite = compute_polygon_points(100, ENTITY_AMPLITUDE)
             .points()
             .to_vec();
 
+        // the board is empty, the first attempt always fits
+        let player_pos = Self::spawn_entity(board, &mut rng);
+        taken_points.push((player_pos, ENTITY_AMPLITUDE));
+
         let enemies_nums = rng.gen_range(2..=5);
-        let enemies_sprites = (0..enemies_nums)
-            .map(|_| {
-                let mut ennemy_pos = Self::spawn_entity();
-                while does_position_overlap(&taken_points, (&ennemy_pos, ENTITY_AMPLITUDE))
-                    || distance_bewteen_two_points(&player_pos, &ennemy_pos) <= 10.0
-                {
-                    ennemy_pos = Self::spawn_entity();
+        let mut enemies_pos = vec![];
+        for _ in 0..enemies_nums {
+            let far_from_player = |pos: &PlotPoint| distance(&player_pos, pos) > MIN_ENEMY_DISTANCE;
+            let candidate =
+                |rng: &mut ChaCha8Rng| (Self::spawn_entity(board, rng), ENTITY_AMPLITUDE);
+            let mut placed = place_entity(&mut rng, &taken_points, candidate, far_from_player);
+            if placed.is_none() && enemies_pos.is_empty() {
+                // a game needs an enemy, even a close one, and the far corner is always free
+                // as only the player has been placed yet
+                placed = place_entity(&mut rng, &taken_points, candidate, |_| true).or(Some((
+                    Self::farthest_spawn_corner(board, &player_pos),
+                    ENTITY_AMPLITUDE,
+                )));
+                if placed.is_some_and(|(pos, _)| !far_from_player(&pos)) {
+                    placement_issues.push("the enemy is closer to you than usual".to_string());
                 }
-                taken_points.push((ennemy_pos, ENTITY_AMPLITUDE));
+            }
+            if let Some(placed) = placed {
+                taken_points.push(placed);
+                enemies_pos.push(placed.0);
+            }
+        }
+        if enemies_pos.len() < enemies_nums {
+            placement_issues.push(format!(
+                "only {} of {enemies_nums} enemies fit on this board",
+                enemies_pos.len()
+            ));
+        }
 
-                (
-                    entity_sprite
-                        .iter()
-                        .map(|&PlotPoint { x, y }| [x + ennemy_pos.x, y + ennemy_pos.y])
-                        .collect::<PlotPoints>()
-                        .points()
-                        .to_vec(),
-                    ennemy_pos,
-                )
-            })
-            .collect::<Vec<_>>();
+        // as many obstacles per area as on the classic board
+        let obstacles_density = board.width * board.height / CLASSIC_BOARD_AREA;
+        let scaled = |obstacles_nums: usize| {
+            ((obstacles_nums as f64 * obstacles_density).round() as usize).max(1)
+        };
+        let obstacles_nums = rng.gen_range(scaled(5)..=scaled(15));
+        let mut obstacles_pos = vec![];
+        for _ in 0..obstacles_nums {
+            let candidate = |rng: &mut ChaCha8Rng| {
+                let amplitude = rng.gen_range(2..=6) as f64;
+                (Self::spawn_entity(board, rng), amplitude)
+            };
+            if let Some(placed) = place_entity(&mut rng, &taken_points, candidate, |_| true) {
+                taken_points.push(placed);
+                obstacles_pos.push(placed);
+            }
+        }
+        if obstacles_pos.len() < obstacles_nums {
+            placement_issues.push(format!(
+                "only {} of {obstacles_nums} obstacles fit on this board",
+                obstacles_pos.len()
+            ));
+        }
 
         EntitiesPos {
-            player: (player_sprite, player_pos),
-            enemies: enemies_sprites,
-            obstacles: obstacles_sprites,
+            player: (translate_sprite(&entity_sprite, &player_pos), player_pos),
+            enemies: enemies_pos
+                .into_iter()
+                .map(|pos| (translate_sprite(&entity_sprite, &pos), pos))
+                .collect(),
+            obstacles: obstacles_pos
+                .into_iter()
+                .map(|(pos, amplitude)| {
+                    // rng.gen_range(3..=15)
+                    let obstacle_sprite = compute_polygon_points(OBSTACLE_SIDES, amplitude)
+                        .points()
+                        .to_vec();
+                    (translate_sprite(&obstacle_sprite, &pos), pos, amplitude)
+                })
+                .collect(),
+            placement_issues,
         }
     }
 
-    /// given all the graph points, detect if it touches obstacles or enemies
-    fn detect_collision(&self, points: &[PlotPoint]) -> Option<Vec<Collision>> {
+    /// given the trajectory, detect where it first touches each obstacle and enemy, in the order of the shot
+    fn detect_collision(&self, trajectory: &Trajectory) -> Option<Vec<Collision>> {
         let mut collisions = vec![];
-        for (frame_id, point) in points.iter().enumerate() {
-            let is_collision = |entity_point: &PlotPoint, amplitude: f64| {
-                let distance = distance_bewteen_two_points(point, entity_point);
-                distance <= amplitude
-            };
+        // the first hit of the trajectory on an entity, following its hitbox
+        let first_hit = |hit: &dyn Fn(&PlotPoint, &PlotPoint) -> Option<f64>| {
+            trajectory.links().find_map(|(frame_id, from, to)| {
+                hit(&from, &to).map(|fraction| {
+                    let hit_point = PlotPoint::new(
+                        from.x + fraction * (to.x - from.x),
+                        from.y + fraction * (to.y - from.y),
+                    );
+                    (frame_id, fraction, hit_point)
+                })
+            })
+        };
 
-            for (ennemy_id, (_, ennemy_pos)) in self.enemies.iter().enumerate() {
-                if is_collision(ennemy_pos, ENTITY_AMPLITUDE) {
-                    let collision = Collision {
-                        entity_point: *ennemy_pos,
-                        collision_type: CollisionType::Ennemy,
-                        frame_id,
-                        entity_id: ennemy_id,
-                    };
-                    if !collisions.contains(&collision) {
-                        collisions.push(collision);
-                    }
-                }
+        for (ennemy_id, (_, ennemy_pos)) in self.enemies.iter().enumerate() {
+            let hit = |from: &PlotPoint, to: &PlotPoint| {
+                segment_circle_intersection(from, to, ennemy_pos, ENTITY_AMPLITUDE)
+            };
+            if let Some((frame_id, fraction, hit_point)) = first_hit(&hit) {
+                collisions.push(Collision {
+                    entity_point: *ennemy_pos,
+                    hit_point,
+                    frame_id,
+                    position: frame_id as f64 + fraction,
+                    collision_type: CollisionType::Ennemy,
+                    entity_id: ennemy_id,
+                });
             }
-            for (obstacle_id, (_, obstacle_pos, amplitude)) in self.obstacles.iter().enumerate() {
-                if is_collision(obstacle_pos, *amplitude) {
-                    let collision = Collision {
-                        entity_point: *obstacle_pos,
-                        collision_type: CollisionType::Obstacle,
-                        frame_id,
-                        entity_id: obstacle_id,
-                    };
-                    if !collisions.contains(&collision) {
-                        collisions.push(collision);
-                    }
-                }
+        }
+        for (obstacle_id, (sprite, obstacle_pos, amplitude)) in self.obstacles.iter().enumerate() {
+            // obstacles are coarse polygons, their circumscribed circle only narrows the search
+            let hit = |from: &PlotPoint, to: &PlotPoint| {
+                segment_circle_intersection(from, to, obstacle_pos, *amplitude)?;
+                segment_polygon_intersection(from, to, sprite)
+            };
+            if let Some((frame_id, fraction, hit_point)) = first_hit(&hit) {
+                collisions.push(Collision {
+                    entity_point: *obstacle_pos,
+                    hit_point,
+                    frame_id,
+                    position: frame_id as f64 + fraction,
+                    collision_type: CollisionType::Obstacle,
+                    entity_id: obstacle_id,
+                });
             }
         }
+        collisions.sort_by(|a, b| a.position.total_cmp(&b.position));
 
         if collisions.is_empty() {
             None
@@ -232,24 +833,214 @@ impl GraphWar {
         }
     }
 
-    fn build_graph(&mut self) {
-        match MathExpression::new(&self.equation) {
-            Ok(math_expr) => {
-                let mut graph_points = compute_line_points(
+    /// parse one of the equation inputs, remembering on it where it is faulty
+    fn parse_equation(
+        &mut self,
+        equation_id: usize,
+        parse: impl FnOnce(&str) -> Result<MathExpression, ExpressionError>,
+    ) -> Result<MathExpression, EvalexprError> {
+        let equation = &mut self.equations[equation_id];
+        parse(&equation.text).map_err(|why| {
+            equation.error_span = why.span;
+            why.error
+        })
+    }
+
+    /// compute the trajectory of the shot, following the current shot mode
+    fn compute_trajectory(&mut self) -> Result<Trajectory, EvalexprError> {
+        let trajectory = match self.shot_mode {
+            ShotMode::Function => {
+                let math_expr = self.parse_equation(0, MathExpression::new)?;
+                self.build_derivative(&math_expr);
+                let from_point = self.anchor_point(&math_expr);
+                let (trajectory, cut_short) = compute_line_points(
+                    &math_expr,
+                    &from_point,
+                    &self.settings.board,
+                    ShotDirection::facing_from(&self.player.1),
+                    &FUNCTION_SAMPLING,
+                    &self.player.1,
+                    self.shot_angle.to_radians(),
+                );
+                if cut_short {
+                    self.messages.insert(
+                        0,
+                        Message::new(
+                            "this line is too wild, it was cut short 🌪".to_string(),
+                            Duration::from_secs(4),
+                            UITypes::Warning,
+                        ),
+                    );
+                }
+                trajectory
+            }
+            ShotMode::FirstOrderOde => {
+                let math_expr = self.parse_equation(0, |raw| {
+                    MathExpression::with_variables(raw, FIRST_ORDER_ODE_VARIABLES)
+                })?;
+                compute_ode_points(
+                    &math_expr,
+                    OdeOrder::First,
+                    &self.player.1,
+                    &self.settings.board,
+                    self.graph_resolution,
+                    ShotDirection::facing_from(&self.player.1),
+                )
+            }
+            ShotMode::SecondOrderOde => {
+                let math_expr = self.parse_equation(0, |raw| {
+                    MathExpression::with_variables(raw, SECOND_ORDER_ODE_VARIABLES)
+                })?;
+                compute_ode_points(
+                    &math_expr,
+                    OdeOrder::Second {
+                        initial_slope: self.ode_initial_slope,
+                    },
+                    &self.player.1,
+                    &self.settings.board,
+                    self.graph_resolution,
+                    ShotDirection::facing_from(&self.player.1),
+                )
+            }
+            ShotMode::Parametric => {
+                let parse = |raw: &str| MathExpression::with_variables(raw, PARAMETRIC_VARIABLES);
+                let x_expr = self.parse_equation(0, parse)?;
+                let y_expr = self.parse_equation(1, parse)?;
+                compute_parametric_points(
+                    (&x_expr, &y_expr),
+                    &self.player.1,
+                    self.parametric_range,
+                    self.graph_resolution,
+                )
+            }
+            ShotMode::Polar => {
+                let math_expr = self.parse_equation(0, |raw| {
+                    MathExpression::with_variables(raw, POLAR_VARIABLES)
+                })?;
+                compute_polar_points(
                     &math_expr,
                     &self.player.1,
-                    (-25, 25),
+                    self.polar_range,
                     self.graph_resolution,
                 )
-                .points()
-                .to_vec();
+            }
+        };
+        let mut trajectory = trajectory;
+        if self.discontinuity_rule() == DiscontinuityRule::Stop {
+            trajectory.keep_first_segment();
+        }
+        Ok(trajectory)
+    }
+
+    /// the settings in force, the ones of the level being played prevail
+    fn anchored_shots(&self) -> bool {
+        self.level_rules
+            .anchored_shots
+            .unwrap_or(self.settings.anchored_shots)
+    }
+
+    fn discontinuity_rule(&self) -> DiscontinuityRule {
+        self.level_rules
+            .discontinuity_rule
+            .unwrap_or(self.settings.discontinuity_rule)
+    }
 
-                if let Some(collisions) = self.detect_collision(&graph_points) {
+    /// the point the function shot is drawn from, lowered by f(0) when the shots are anchored
+    fn anchor_point(&mut self, math_expr: &MathExpression) -> PlotPoint {
+        let mut anchor_point = self.player.1;
+        if self.anchored_shots() {
+            match math_expr.compute(0.0) {
+                Ok(offset) if offset.is_finite() => {
+                    anchor_point.y -= offset;
+                    self.anchor_offset = Some(offset);
+                }
+                _ => self.messages.insert(
+                    0,
+                    Message::new(
+                        "f(0) is not defined, this shot can't be anchored on you".to_string(),
+                        Duration::from_secs(4),
+                        UITypes::Warning,
+                    ),
+                ),
+            }
+        }
+        anchor_point
+    }
+
+    fn build_derivative(&mut self, math_expr: &MathExpression) {
+        self.derivative_cached = match math_expr.derivative() {
+            Ok(derivative) => {
+                // the derivative lives in the same rotated frame as the shot
+                let (trajectory, _) = compute_line_points(
+                    &derivative,
+                    &self.player.1,
+                    &self.settings.board,
+                    ShotDirection::facing_from(&self.player.1),
+                    &DERIVATIVE_SAMPLING,
+                    &self.player.1,
+                    self.shot_angle.to_radians(),
+                );
+                Some((derivative.to_string(), trajectory))
+            }
+            Err(why) => {
+                if self.show_derivative {
+                    let reason = match why {
+                        EvalexprError::CustomMessage(reason) => reason,
+                        _ => "this line can't be derived".to_string(),
+                    };
+                    self.messages.insert(
+                        0,
+                        Message::new(reason, Duration::from_secs(4), UITypes::Warning),
+                    );
+                }
+                None
+            }
+        };
+    }
+
+    fn build_graph(&mut self) {
+        self.derivative_cached = None;
+        self.anchor_offset = None;
+        self.graph_exit_point = None;
+        match self.compute_trajectory() {
+            Ok(trajectory) if trajectory.is_empty() => self.messages.insert(
+                0,
+                Message::new(
+                    "this line is not defined in front of you 🤔".to_string(),
+                    Duration::from_secs(4),
+                    UITypes::Error,
+                ),
+            ),
+            Ok(mut trajectory) => {
+                // like for obstacles, the shot ends where it leaves the board
+                match find_board_exit(&trajectory, &self.settings.board) {
+                    Some(BoardExit::Crossing(frame_id, exit_point)) => {
+                        trajectory.cut_at(frame_id, exit_point);
+                        self.graph_exit_point = Some(exit_point);
+                    }
+                    // it has left the board by the discontinuity before the point
+                    Some(BoardExit::OffBoard(frame_id)) if frame_id > 0 => {
+                        trajectory.truncate(frame_id - 1)
+                    }
+                    Some(BoardExit::OffBoard(_)) => {
+                        self.messages.insert(
+                            0,
+                            Message::new(
+                                "this line starts out of the board 🤔".to_string(),
+                                Duration::from_secs(4),
+                                UITypes::Error,
+                            ),
+                        );
+                        return;
+                    }
+                    None => {}
+                }
+                if let Some(collisions) = self.detect_collision(&trajectory) {
                     for collision in collisions {
                         match collision.collision_type {
                             CollisionType::Obstacle => {
                                 // when a obstacle is encounter it's the graph end. Thus we stop the graph points at this place
-                                graph_points = graph_points[..=collision.frame_id].to_vec();
+                                trajectory.cut_at(collision.frame_id, collision.hit_point);
                                 break;
                             }
                             CollisionType::Ennemy => self
@@ -259,7 +1050,8 @@ impl GraphWar {
                     }
                 }
 
-                self.graph_cached_points = Some(graph_points);
+                self.graph_animation_speed = (trajectory.points.len() / ANIMATION_FRAMES).max(1);
+                self.graph_cached_points = Some(trajectory);
                 self.graph_animation_frame = 0;
             }
             Err(why) => {
@@ -275,15 +1067,192 @@ impl GraphWar {
         }
     }
 
+    /// insert a symbol at the cursor of an equation input (or at its end when it has no cursor)
+    fn insert_in_equation(
+        &mut self,
+        ctx: &egui::Context,
+        text_edit_id: egui::Id,
+        equation_id: usize,
+        symbol: char,
+    ) {
+        let equation = &mut self.equations[equation_id];
+        let mut state = TextEditState::load(ctx, text_edit_id).unwrap_or_default();
+        let char_idx = state
+            .ccursor_range()
+            .map_or(equation.text.chars().count(), |range| range.primary.index);
+        let byte_idx = equation
+            .text
+            .char_indices()
+            .nth(char_idx)
+            .map_or(equation.text.len(), |(byte_idx, _)| byte_idx);
+
+        equation.text.insert(byte_idx, symbol);
+        state.set_ccursor_range(Some(CCursorRange::one(CCursor::new(char_idx + 1))));
+        state.store(ctx, text_edit_id);
+
+        equation.error_span = None;
+        self.hide_graph();
+    }
+
     fn hide_graph(&mut self) {
-        self.graph_cached_points = None
+        self.graph_cached_points = None;
+        self.derivative_cached = None;
+        self.anchor_offset = None;
+        self.graph_exit_point = None;
+    }
+
+    /// the plot height that leaves room for the controls and messages below it
+    /// the plot fills the space the controls leave, with the aspect ratio of the board
+    fn plot_height(board: &BoardConfig, available_size: egui::Vec2) -> f32 {
+        (available_size.x / board.aspect_ratio() as f32).min(available_size.y)
+    }
+
+    fn render_messages(&self, ui: &mut egui::Ui) {
+        for msg in &self.messages {
+            ui.add_space(5.0);
+            ui.label(msg.render());
+        }
     }
 
-    fn spawn_entity() -> PlotPoint {
-        let mut rng = rand::thread_rng();
+    /// the board of the editor, which replaces the game: entities are placed by pressing and
+    /// dragging the pointer
+    fn show_editor_board(&mut self, ui: &mut egui::Ui) {
+        let Some(editor) = self.editor.as_mut() else {
+            return;
+        };
+        let plot_height = Self::plot_height(&editor.level.board, ui.available_size());
+
+        ui.vertical_centered_justified(|ui| {
+            let EntitiesPos {
+                player,
+                enemies,
+                obstacles,
+                ..
+            } = Self::level_entities(&editor.level);
+            let plot_response =
+                get_app_plot(&editor.level.board)
+                    .height(plot_height)
+                    .show(ui, |plot_ui| {
+                        plot_ui.render_player(&player.0);
+                        plot_ui.render_ennemies(&enemies);
+                        plot_ui.render_obstacles(&obstacles);
+                    });
+            let (response, transform) = (plot_response.response, plot_response.transform);
+            if let Some(pointer) = response.interact_pointer_pos() {
+                let point = transform.value_from_position(pointer);
+                if response.drag_started() {
+                    editor.press(point);
+                } else if response.dragged() {
+                    editor.drag(point);
+                }
+            }
+            if response.drag_released() {
+                editor.release();
+            }
+        });
+    }
+
+    /// the tools of the editor, under its board
+    fn show_editor_tools(&mut self, ui: &mut egui::Ui) {
+        let Some(editor) = self.editor.as_mut() else {
+            self.editing = false;
+            return;
+        };
+
+        ui.horizontal(|ui| {
+            for tool in EditorTool::ALL {
+                ui.selectable_value(
+                    &mut editor.tool,
+                    tool,
+                    rich_text(tool.name(), UITypes::Neutral),
+                );
+            }
+            let board = &mut editor.level.board;
+            for (value, prefix) in [(&mut board.width, "width "), (&mut board.height, "height ")] {
+                ui.add(
+                    egui::DragValue::new(value)
+                        .speed(0.5)
+                        .clamp_range(BoardConfig::MIN_SIZE..=BoardConfig::MAX_SIZE)
+                        .prefix(prefix),
+                );
+            }
+        });
+        ui.label(rich_text(
+            "press the board to use the tool, drag an entity to move it, drag the rim of an obstacle to resize it",
+            UITypes::Info,
+        ));
+
+        let mut problems = None;
+        ui.horizontal(|ui| {
+            ui.label(rich_text("level file", UITypes::Neutral));
+            ui.add(
+                egui::TextEdit::singleline(&mut editor.save_path).hint_text("path/to/level.toml"),
+            );
+            if ui.button(rich_text("save 💾", UITypes::Neutral)).clicked() {
+                let path = editor.save_path.trim().to_string();
+                problems = Some(match editor.level.save(Path::new(&path)) {
+                    Ok(()) => Message::new(
+                        format!("level saved to {path}"),
+                        Duration::from_secs(4),
+                        UITypes::Success,
+                    ),
+                    Err(why) => {
+                        Message::new(why.to_string(), Duration::from_secs(8), UITypes::Error)
+                    }
+                });
+            }
+            if ui
+                .button(rich_text("test play ▶", UITypes::Neutral))
+                .clicked()
+            {
+                match editor.level.validate() {
+                    Ok(()) => self.test_playing = true,
+                    Err(why) => {
+                        problems = Some(Message::new(
+                            why.to_string(),
+                            Duration::from_secs(8),
+                            UITypes::Error,
+                        ))
+                    }
+                }
+            }
+            if ui
+                .button(rich_text("quit editor ✖", UITypes::Neutral))
+                .clicked()
+            {
+                self.editing = false;
+            }
+        });
+        if let Some(msg) = problems {
+            self.messages.insert(0, msg);
+        }
+
+        if self.test_playing {
+            let level = editor.level.clone();
+            self.play_level(&level);
+        } else if !self.editing {
+            self.editor = None;
+        }
+    }
+
+    /// the corner of the spawn area the farthest from `pos`
+    fn farthest_spawn_corner(board: &BoardConfig, pos: &PlotPoint) -> PlotPoint {
+        let ((x_min, x_max), (y_min, y_max)) = (board.x_bounds(), board.y_bounds());
+        let farthest = |value: f64, (min, max): (f64, f64)| match value < (min + max) / 2.0 {
+            true => max - board.margin,
+            false => min + board.margin,
+        };
+        PlotPoint::new(
+            farthest(pos.x, (x_min, x_max)),
+            farthest(pos.y, (y_min, y_max)),
+        )
+    }
+
+    fn spawn_entity(board: &BoardConfig, rng: &mut ChaCha8Rng) -> PlotPoint {
+        let ((x_min, x_max), (y_min, y_max)) = (board.x_bounds(), board.y_bounds());
         let (x, y) = (
-            rng.gen_range(-25..=25) as f64,
-            rng.gen_range(-25..=25) as f64,
+            rng.gen_range(x_min + board.margin..=x_max - board.margin),
+            rng.gen_range(y_min + board.margin..=y_max - board.margin),
         );
         PlotPoint { x, y }
     }
@@ -291,8 +1260,28 @@ impl GraphWar {
 
 impl eframe::App for GraphWar {
     fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
+        self.show(ctx);
+    }
+}
+
+impl GraphWar {
+    /// lay out a frame of the game, or of the editor
+    fn show(&mut self, ctx: &egui::Context) {
         if self.enemies.is_empty() {
-            self.new_game();
+            match self.test_playing {
+                true => {
+                    self.messages.insert(
+                        0,
+                        Message::new(
+                            "level cleared, back to the editor".to_string(),
+                            Duration::from_secs(4),
+                            UITypes::Success,
+                        ),
+                    );
+                    self.open_editor();
+                }
+                false => self.new_game(),
+            }
         }
 
         let is_messages = !self.messages.is_empty();
@@ -300,75 +1289,703 @@ impl eframe::App for GraphWar {
             self.messages.retain(|msg| !msg.is_expired())
         }
 
-        egui::CentralPanel::default().show(ctx, |ui| {
-            let window_size = ui.available_size();
+        // Button, Input and Messages, under the plot
+        egui::TopBottomPanel::bottom("controls").show(ctx, |ui| {
+            if self.editing {
+                self.show_editor_tools(ui);
+            } else {
+                ui.vertical_centered_justified(|ui| {
+                    ui.horizontal(|ui| {
+                        for shot_mode in ShotMode::ALL {
+                            if ui
+                                .selectable_value(
+                                    &mut self.shot_mode,
+                                    shot_mode,
+                                    rich_text(shot_mode.name(), UITypes::Neutral),
+                                )
+                                .changed()
+                            {
+                                self.hide_graph();
+                            }
+                        }
+                        if self.shot_mode == ShotMode::SecondOrderOde
+                            && ui
+                                .add(
+                                    egui::DragValue::new(&mut self.ode_initial_slope)
+                                        .speed(0.1)
+                                        .prefix("y'(0) = "),
+                                )
+                                .changed()
+                        {
+                            self.hide_graph();
+                        }
+                    });
 
-            // PLOT
-            ui.vertical_centered_justified(|ui| {
-                let max_plot_size = window_size.x.min(window_size.y)
-                    - 80.0
-                    - if is_messages {
-                        25.0 * self.messages.len() as f32
-                    } else {
-                        0.0
+                    let mut equation_text_ids = vec![];
+                    for (equation_id, label) in self.shot_mode.equation_labels().iter().enumerate()
+                    {
+                        let equation = &mut self.equations[equation_id];
+                        let name_label = ui.label(rich_text(label, UITypes::Neutral));
+                        let error_span = equation.error_span.clone();
+                        let mut layouter = |ui: &egui::Ui, text: &str, wrap_width: f32| {
+                            let mut layout_job = highlighted_layout_job(
+                                text,
+                                error_span.as_ref(),
+                                TextStyle::Body.resolve(ui.style()),
+                            );
+                            layout_job.wrap.max_width = wrap_width;
+                            ui.fonts(|fonts| fonts.layout_job(layout_job))
+                        };
+                        let equation_text_ui = ui
+                            .add(
+                                egui::TextEdit::singleline(&mut equation.text)
+                                    .layouter(&mut layouter),
+                            )
+                            .labelled_by(name_label.id);
+                        if equation_text_ui.changed() {
+                            equation.error_span = None;
+                            self.hide_graph();
+                        }
+                        if equation_text_ui.has_focus() {
+                            self.palette_target = equation_id;
+                        }
+                        equation_text_ids.push(equation_text_ui.id);
+                    }
+
+                    if self.shot_mode == ShotMode::Function
+                        && ui
+                            .add(
+                                egui::DragValue::new(&mut self.shot_angle)
+                                    .speed(1.0)
+                                    .clamp_range(-180.0..=180.0)
+                                    .prefix("firing angle ")
+                                    .suffix("°"),
+                            )
+                            .on_hover_text("rotates the line around the player")
+                            .changed()
+                    {
+                        self.hide_graph();
+                    }
+
+                    let parameter_range = match self.shot_mode {
+                        ShotMode::Parametric => Some((&mut self.parametric_range, "t")),
+                        ShotMode::Polar => Some((&mut self.polar_range, "θ")),
+                        _ => None,
                     };
-                let plot = get_app_plot().height(max_plot_size);
-                // .width(min_square_size);
+                    if let Some(((from, to), parameter)) = parameter_range {
+                        let range_changed = ui
+                            .horizontal(|ui| {
+                                let from_ui = ui.add(
+                                    egui::DragValue::new(from)
+                                        .speed(0.1)
+                                        .prefix(format!("{parameter} from ")),
+                                );
+                                let to_ui =
+                                    ui.add(egui::DragValue::new(to).speed(0.1).prefix("to "));
+                                from_ui.changed() || to_ui.changed()
+                            })
+                            .inner;
+                        if range_changed {
+                            // keep the range sound, and the amount of points reasonable
+                            *from = from.clamp(-MAX_PARAMETER, MAX_PARAMETER);
+                            *to = to.clamp(*from, MAX_PARAMETER);
+                            self.hide_graph();
+                        }
+                    }
 
-                plot.show(ui, |plot_ui| {
-                    if let Some(points) = &self.graph_cached_points {
-                        plot_ui.render_graph(points, self.graph_animation_frame);
-                        // animation manager: while current frame is not equal to the last frame, continue animation
-                        if self.graph_animation_frame < points.len() - 1 {
-                            // delete enemies from app and UI and the animation touch them
+                    // symbol palette
+                    let palette_target = self.palette_target.min(equation_text_ids.len() - 1);
+                    ui.horizontal_wrapped(|ui| {
+                        for (glyph, plain) in UNICODE_SYMBOLS {
+                            if ui
+                                .button(rich_text(&glyph.to_string(), UITypes::Neutral))
+                                .on_hover_text(format!("same as typing \"{plain}\""))
+                                .clicked()
                             {
-                                // get all the enemies touched before the nth frame...
-                                let enemies_touched = self
-                                    .enemies_killed
-                                    .iter()
-                                    .filter(|(_, frame_id)| frame_id <= &self.graph_animation_frame)
-                                    .map(|(entity_pos, _)| *entity_pos)
-                                    .collect::<Vec<_>>();
-                                // ...and delete them
-                                self.enemies
-                                    .retain(|(_, pos)| !enemies_touched.contains(pos));
+                                let text_edit_id = equation_text_ids[palette_target];
+                                self.insert_in_equation(ctx, text_edit_id, palette_target, glyph);
+                                ctx.memory_mut(|memory| memory.request_focus(text_edit_id));
+                            }
+                        }
+                        if ui
+                            .button(rich_text("functions ❓", UITypes::Neutral))
+                            .clicked()
+                        {
+                            self.show_functions_help = !self.show_functions_help;
+                        }
+                        if ui
+                            .button(rich_text("settings ⚙", UITypes::Neutral))
+                            .clicked()
+                        {
+                            self.show_settings = !self.show_settings;
+                        }
+                        if ui
+                            .button(rich_text("editor ✏", UITypes::Neutral))
+                            .on_hover_text(match self.test_playing {
+                                true => "back to the level being edited",
+                                false => "build a level, starting from this board",
+                            })
+                            .clicked()
+                        {
+                            self.open_editor();
+                        }
+                    });
+
+                    ui.add_space(5.0);
+                    if ui
+                        .button(rich_text("Shoot! 🎯", UITypes::Neutral))
+                        .clicked()
+                    {
+                        self.build_graph();
+                    }
+                    if let Some(offset) = self.anchor_offset {
+                        ui.label(rich_text(
+                            &format!(
+                                "anchored on you: the line is shifted by -f(0) = {:.3}",
+                                0.0 - offset // rather than -offset, which prints "-0.000"
+                            ),
+                            UITypes::Info,
+                        ));
+                    }
+                    ui.checkbox(
+                        &mut self.show_derivative,
+                        rich_text("show derivative", UITypes::Neutral),
+                    );
+                    if let (true, Some((formula, _))) =
+                        (self.show_derivative, &self.derivative_cached)
+                    {
+                        ui.label(rich_text(&format!("f'(x) = {formula}"), UITypes::Info));
+                    }
+
+                    ui.horizontal(|ui| {
+                        ui.label(rich_text("board seed", UITypes::Neutral));
+                        ui.add(
+                            egui::TextEdit::singleline(&mut self.seed_input).desired_width(170.0),
+                        );
+                        if ui
+                            .button(rich_text("replay ↺", UITypes::Neutral))
+                            .on_hover_text("the same seed on the same board gives the same map")
+                            .clicked()
+                        {
+                            match self.seed_input.trim().parse() {
+                                Ok(seed) => self.new_game_with_seed(seed),
+                                Err(_) => self.messages.insert(
+                                    0,
+                                    Message::new(
+                                        "a seed is a whole number, from 0 to 18446744073709551615"
+                                            .to_string(),
+                                        Duration::from_secs(4),
+                                        UITypes::Error,
+                                    ),
+                                ),
                             }
+                        }
+                        if ui
+                            .button(rich_text("new board 🎲", UITypes::Neutral))
+                            .clicked()
+                        {
+                            self.new_game();
+                        }
+                    });
+                });
+            }
+            self.render_messages(ui);
+        });
+
+        // PLOT, in the space the controls leave
+        egui::CentralPanel::default().show(ctx, |ui| {
+            if self.editing {
+                self.show_editor_board(ui);
+                return;
+            }
+
+            ui.vertical_centered_justified(|ui| {
+                let board = &self.settings.board;
+                // the plot width follows the board aspect ratio
+                let plot =
+                    get_app_plot(board).height(Self::plot_height(board, ui.available_size()));
 
+                plot.show(ui, |plot_ui| {
+                    if let Some(trajectory) = &self.graph_cached_points {
+                        plot_ui.render_graph(trajectory, self.graph_animation_frame);
+                        // delete enemies from app and UI and the animation touch them
+                        // (once more after the last frame, which the speed may have jumped over)
+                        {
+                            // get all the enemies touched before the nth frame...
+                            let enemies_touched = self
+                                .enemies_killed
+                                .iter()
+                                .filter(|(_, frame_id)| frame_id <= &self.graph_animation_frame)
+                                .map(|(entity_pos, _)| *entity_pos)
+                                .collect::<Vec<_>>();
+                            // ...and delete them
+                            self.enemies
+                                .retain(|(_, pos)| !enemies_touched.contains(pos));
+                        }
+                        // animation manager: while current frame is not equal to the last frame, continue animation
+                        if self.graph_animation_frame < trajectory.points.len() - 1 {
                             self.graph_animation_frame += self.graph_animation_speed;
                             ctx.request_repaint();
+                        } else if let Some(exit_point) = &self.graph_exit_point {
+                            plot_ui.render_exit_point(exit_point);
                         }
                     }
+                    if let (true, Some((_, trajectory))) =
+                        (self.show_derivative, &self.derivative_cached)
+                    {
+                        plot_ui.render_derivative(trajectory);
+                    }
                     plot_ui.render_player(&self.player.0);
                     plot_ui.render_ennemies(&self.enemies);
                     plot_ui.render_obstacles(&self.obstacles);
                 });
             });
+        });
 
-            // Button, Input and Messages
-            ui.vertical_centered_justified(|ui| {
-                let name_label = ui.label(rich_text("Line equation:", UITypes::Neutral));
-                let equation_text_ui = ui
-                    .text_edit_singleline(&mut self.equation)
-                    .labelled_by(name_label.id);
-                if equation_text_ui.changed() {
-                    self.hide_graph();
-                }
+        egui::Window::new("Functions & constants")
+            .open(&mut self.show_functions_help)
+            .default_width(450.0)
+            .show(ctx, render_functions_help);
 
-                ui.add_space(5.0);
-                if ui
-                    .button(rich_text("Shoot! 🎯", UITypes::Neutral))
-                    .clicked()
+        let mut settings_changed = false;
+        let mut board_changed = false;
+        let mut level_requested = false;
+        egui::Window::new("Settings")
+            .open(&mut self.show_settings)
+            .show(ctx, |ui| {
+                let level_rules = &self.level_rules;
+                if level_rules.anchored_shots.is_some() || level_rules.discontinuity_rule.is_some()
                 {
-                    self.build_graph();
+                    ui.label(rich_text(
+                        "the level being played sets some of these",
+                        UITypes::Info,
+                    ));
                 }
+                ui.add_enabled_ui(level_rules.anchored_shots.is_none(), |ui| {
+                    settings_changed |= ui
+                        .checkbox(
+                            &mut self.settings.anchored_shots,
+                            rich_text("anchored shots", UITypes::Neutral),
+                        )
+                        .on_hover_text(
+                            "the line y = f(x) - f(0) passes through you, as in the original game",
+                        )
+                        .changed();
+                });
+                ui.add_enabled_ui(level_rules.discontinuity_rule.is_none(), |ui| {
+                    ui.horizontal(|ui| {
+                        ui.label(rich_text("at a discontinuity, the shot", UITypes::Neutral));
+                        for (rule, name) in [
+                            (DiscontinuityRule::Stop, "stops"),
+                            (DiscontinuityRule::Jump, "jumps over it"),
+                        ] {
+                            settings_changed |= ui
+                                .selectable_value(
+                                    &mut self.settings.discontinuity_rule,
+                                    rule,
+                                    rich_text(name, UITypes::Neutral),
+                                )
+                                .changed();
+                        }
+                    })
+                });
 
-                if is_messages {
-                    for msg in &self.messages {
-                        ui.add_space(5.0);
-                        ui.label(msg.render());
+                ui.separator();
+                let board = &mut self.settings.board;
+                ui.horizontal(|ui| {
+                    ui.label(rich_text("board", UITypes::Neutral));
+                    for (name, preset) in BoardConfig::PRESETS {
+                        if ui
+                            .selectable_label(*board == preset, rich_text(name, UITypes::Neutral))
+                            .clicked()
+                        {
+                            *board = preset;
+                            board_changed = true;
+                        }
                     }
-                }
+                });
+                ui.horizontal(|ui| {
+                    for (value, prefix, range) in [
+                        (
+                            &mut board.width,
+                            "width ",
+                            BoardConfig::MIN_SIZE..=BoardConfig::MAX_SIZE,
+                        ),
+                        (
+                            &mut board.height,
+                            "height ",
+                            BoardConfig::MIN_SIZE..=BoardConfig::MAX_SIZE,
+                        ),
+                        (&mut board.margin, "margin ", 0.0..=BoardConfig::MAX_MARGIN),
+                    ] {
+                        let response = ui.add(
+                            egui::DragValue::new(value)
+                                .speed(0.5)
+                                .clamp_range(range)
+                                .prefix(prefix),
+                        );
+                        // a new board once the value is set, not for each step of a drag
+                        match response.dragged() {
+                            true => self.board_resizing |= response.changed(),
+                            false => board_changed |= response.changed(),
+                        }
+                        if response.drag_released() && self.board_resizing {
+                            self.board_resizing = false;
+                            board_changed = true;
+                        }
+                    }
+                });
+
+                ui.separator();
+                ui.horizontal(|ui| {
+                    ui.label(rich_text("level file", UITypes::Neutral));
+                    ui.add(
+                        egui::TextEdit::singleline(&mut self.level_path_input)
+                            .hint_text("path/to/level.toml"),
+                    );
+                    level_requested = ui
+                        .button(rich_text("load 📂", UITypes::Neutral))
+                        .on_hover_text("play a hand-authored board, see levels/example.toml")
+                        .clicked();
+                });
+            });
+        if level_requested {
+            let level_path = self.level_path_input.trim().to_string();
+            self.load_level_file(&level_path);
+        } else if board_changed {
+            self.new_game();
+        } else if settings_changed {
+            self.hide_graph();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn point(x: f64, y: f64) -> PlotPoint {
+        PlotPoint::new(x, y)
+    }
+
+    fn assert_near(actual: Option<f64>, expected: f64) {
+        let actual = actual.expect("an intersection");
+        assert!(
+            (actual - expected).abs() < 1e-9,
+            "{actual} instead of {expected}"
+        );
+    }
+
+    /// the bottom of each text drawn in a frame of `size`, once the layout has settled
+    fn rendered_texts(game: &mut GraphWar, size: egui::Vec2) -> Vec<(String, f32)> {
+        let ctx = egui::Context::default();
+        let mut output = None;
+        for _ in 0..3 {
+            let input = egui::RawInput {
+                screen_rect: Some(egui::Rect::from_min_size(egui::Pos2::ZERO, size)),
+                ..Default::default()
+            };
+            output = Some(ctx.run(input, |ctx| game.show(ctx)));
+        }
+        output
+            .unwrap()
+            .shapes
+            .into_iter()
+            .filter_map(|clipped| match clipped.1 {
+                egui::Shape::Text(text) => Some((
+                    text.galley.job.text.clone(),
+                    text.pos.y + text.galley.size().y,
+                )),
+                _ => None,
             })
-        });
+            .collect()
+    }
+
+    #[test]
+    fn controls_fit_in_the_window() {
+        for size in [
+            egui::vec2(1920.0, 1040.0),
+            egui::vec2(1000.0, 1000.0),
+            egui::vec2(1000.0, 600.0),
+        ] {
+            let mut game = GraphWar {
+                shot_mode: ShotMode::Parametric, // the mode with the most controls
+                show_derivative: true,
+                ..Default::default()
+            };
+            for i in 0..4 {
+                game.messages.push(Message::new(
+                    format!("message {i}"),
+                    Duration::from_secs(60),
+                    UITypes::Error,
+                ));
+            }
+            let texts = rendered_texts(&mut game, size);
+            for expected in ["Shoot! 🎯", "show derivative", "new board 🎲", "message 3"] {
+                let bottom = texts
+                    .iter()
+                    .find(|(text, _)| text == expected)
+                    .map(|(_, bottom)| *bottom);
+                assert!(
+                    bottom.is_some_and(|bottom| bottom <= size.y),
+                    "{expected} in {size:?}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn boards_always_have_an_enemy() {
+        let cramped = BoardConfig::new(
+            BoardConfig::MIN_SIZE,
+            BoardConfig::MIN_SIZE,
+            BoardConfig::MAX_MARGIN,
+        );
+        for seed in 0..50 {
+            let entities = GraphWar::compute_all_entities_position(&cramped, seed);
+            assert!(!entities.enemies.is_empty(), "seed {seed}");
+        }
+    }
+
+    fn entities(
+        player: PlotPoint,
+        enemies: &[PlotPoint],
+        obstacles: &[(PlotPoint, f64)],
+    ) -> EntitiesPos {
+        let sprite = |pos: &PlotPoint, amplitude: f64| {
+            translate_sprite(compute_polygon_points(20, amplitude).points(), pos)
+        };
+        EntitiesPos {
+            player: (sprite(&player, ENTITY_AMPLITUDE), player),
+            enemies: enemies
+                .iter()
+                .map(|pos| (sprite(pos, ENTITY_AMPLITUDE), *pos))
+                .collect(),
+            obstacles: obstacles
+                .iter()
+                .map(|(pos, amplitude)| (sprite(pos, *amplitude), *pos, *amplitude))
+                .collect(),
+            placement_issues: vec![],
+        }
+    }
+
+    #[test]
+    fn surrounded_enemies_are_moved() {
+        let board = BoardConfig::default();
+        let enemy = point(10.0, 0.0);
+        // a ring of overlapping obstacles around the enemy
+        let ring: Vec<_> = (0..16)
+            .map(|i| {
+                let angle = i as f64 * TAU / 16.0;
+                (point(10.0 + 3.5 * angle.cos(), 3.5 * angle.sin()), 1.0)
+            })
+            .collect();
+        let mut entities = entities(point(-20.0, 0.0), &[enemy], &ring);
+        let grid = ReachabilityGrid::new(&board, &entities.player.1, &entities.obstacles);
+        assert!(!grid.can_hit(&enemy, ENTITY_AMPLITUDE));
+
+        assert!(make_enemies_reachable(&mut entities, &board, 0));
+        let moved = entities.enemies[0].1;
+        assert!(moved != enemy);
+        assert!(grid.can_hit(&moved, ENTITY_AMPLITUDE));
+        assert!(distance(&moved, &entities.player.1) > MIN_ENEMY_DISTANCE);
+        assert!(ring
+            .iter()
+            .all(|(pos, amplitude)| distance(&moved, pos) > amplitude + ENTITY_AMPLITUDE));
+    }
+
+    #[test]
+    fn reachable_enemies_are_kept() {
+        let board = BoardConfig::default();
+        let enemies = [point(10.0, 0.0), point(15.0, -12.0)];
+        // the obstacle is in the way of straight lines, not of every curve
+        let mut entities = entities(point(-20.0, 0.0), &enemies, &[(point(0.0, 0.0), 4.0)]);
+        assert!(make_enemies_reachable(&mut entities, &board, 0));
+        let positions: Vec<_> = entities.enemies.iter().map(|(_, pos)| *pos).collect();
+        assert!(positions == enemies);
+    }
+
+    #[test]
+    fn generated_enemies_are_reachable() {
+        let board = BoardConfig::default();
+        for seed in 0..20 {
+            let (entities, _) = GraphWar::generate_solvable_entities(&board, seed);
+            let grid = ReachabilityGrid::new(&board, &entities.player.1, &entities.obstacles);
+            assert!(
+                entities
+                    .enemies
+                    .iter()
+                    .all(|(_, pos)| grid.can_hit(pos, ENTITY_AMPLITUDE)),
+                "seed {seed}"
+            );
+        }
+    }
+
+    #[test]
+    fn seeds_always_give_the_same_board() {
+        let board = BoardConfig::PRESETS[1].1;
+        let positions = |entities: &EntitiesPos| {
+            let enemies = entities.enemies.iter().map(|(_, pos)| (pos.x, pos.y));
+            let obstacles = entities
+                .obstacles
+                .iter()
+                .map(|(_, pos, amplitude)| (pos.x, pos.y + amplitude));
+            std::iter::once((entities.player.1.x, entities.player.1.y))
+                .chain(enemies)
+                .chain(obstacles)
+                .collect::<Vec<_>>()
+        };
+        let (entities, seed) = GraphWar::generate_solvable_entities(&board, 42);
+        let (same_entities, same_seed) = GraphWar::generate_solvable_entities(&board, 42);
+        assert_eq!(seed, same_seed);
+        assert_eq!(positions(&entities), positions(&same_entities));
+        // shared seeds must give the same board whatever the build and the platform
+        let expected = [(14.187903, 17.110465), (-27.303208, -7.294459)];
+        for ((x, y), (expected_x, expected_y)) in positions(&entities).into_iter().zip(expected) {
+            assert!((x - expected_x).abs() < 1e-6 && (y - expected_y).abs() < 1e-6);
+        }
+    }
+
+    #[test]
+    fn edited_levels_keep_their_obstacles_shape() {
+        let mut game = GraphWar::default();
+        let level: Level = toml::from_str(include_str!("../levels/example.toml")).unwrap();
+        game.play_level(&level);
+        let sides: Vec<_> = game
+            .current_level()
+            .obstacles
+            .iter()
+            .map(|obstacle| obstacle.sides)
+            .collect();
+        assert_eq!(sides, [6, 20, 4]);
+    }
+
+    #[test]
+    fn edited_levels_only_keep_the_level_rules() {
+        let mut game = GraphWar::default();
+        game.settings.anchored_shots = false;
+        let rules = game.current_level().rules;
+        assert!(rules.anchored_shots.is_none() && rules.discontinuity_rule.is_none());
+
+        let mut level: Level = toml::from_str(include_str!("../levels/example.toml")).unwrap();
+        level.rules = LevelRules {
+            anchored_shots: None,
+            discontinuity_rule: Some(DiscontinuityRule::Jump),
+        };
+        game.play_level(&level);
+        let rules = game.current_level().rules;
+        assert!(rules.anchored_shots.is_none());
+        assert!(rules.discontinuity_rule == Some(DiscontinuityRule::Jump));
+    }
+
+    #[test]
+    fn board_exit() {
+        let board = BoardConfig::default();
+        let inside: Trajectory = vec![point(0.0, 0.0), point(10.0, 5.0)].into();
+        assert_eq!(find_board_exit(&inside, &board), None);
+
+        let leaving: Trajectory =
+            vec![point(0.0, 0.0), point(20.0, 10.0), point(30.0, 20.0)].into();
+        assert_eq!(
+            find_board_exit(&leaving, &board),
+            Some(BoardExit::Crossing(1, point(25.0, 15.0)))
+        );
+        // by a corner
+        let leaving: Trajectory = vec![point(20.0, 20.0), point(30.0, 30.0)].into();
+        assert_eq!(
+            find_board_exit(&leaving, &board),
+            Some(BoardExit::Crossing(0, point(25.0, 25.0)))
+        );
+    }
+
+    #[test]
+    fn board_exit_of_shots_starting_off_the_board() {
+        let board = BoardConfig::default();
+        // like a polar shot r = 40, which comes on the board afterwards
+        let outside: Trajectory = vec![point(40.0, 0.0), point(20.0, 0.0), point(0.0, 0.0)].into();
+        assert_eq!(
+            find_board_exit(&outside, &board),
+            Some(BoardExit::OffBoard(0))
+        );
+        let lonely: Trajectory = vec![point(-40.0, 0.0)].into();
+        assert_eq!(
+            find_board_exit(&lonely, &board),
+            Some(BoardExit::OffBoard(0))
+        );
+    }
+
+    #[test]
+    fn segment_circle() {
+        let center = point(0.0, 0.0);
+        // enters the circle a quarter of the way
+        assert_near(
+            segment_circle_intersection(&point(-2.0, 0.0), &point(2.0, 0.0), &center, 1.0),
+            0.25,
+        );
+        // passes by, stops before it, or starts inside
+        assert_eq!(
+            segment_circle_intersection(&point(-2.0, 2.0), &point(2.0, 2.0), &center, 1.0),
+            None
+        );
+        assert_eq!(
+            segment_circle_intersection(&point(-3.0, 0.0), &point(-2.0, 0.0), &center, 1.0),
+            None
+        );
+        assert_near(
+            segment_circle_intersection(&point(0.5, 0.0), &point(3.0, 0.0), &center, 1.0),
+            0.0,
+        );
+        // a segment reduced to a point, outside
+        assert_eq!(
+            segment_circle_intersection(&point(2.0, 0.0), &point(2.0, 0.0), &center, 1.0),
+            None
+        );
+    }
+
+    #[test]
+    fn segment_polygon() {
+        let square = [
+            point(-1.0, -1.0),
+            point(1.0, -1.0),
+            point(1.0, 1.0),
+            point(-1.0, 1.0),
+        ];
+        assert_near(
+            segment_polygon_intersection(&point(-3.0, 0.0), &point(1.0, 0.0), &square),
+            0.5,
+        );
+        // the corners of the square stick out of its inscribed circle
+        assert_near(
+            segment_polygon_intersection(&point(-3.0, 0.9), &point(3.0, 0.9), &square),
+            1.0 / 3.0,
+        );
+        assert_eq!(
+            segment_polygon_intersection(&point(-3.0, 2.0), &point(3.0, 2.0), &square),
+            None
+        );
+        assert_near(
+            segment_polygon_intersection(&point(0.0, 0.0), &point(3.0, 3.0), &square),
+            0.0,
+        );
+        assert!(is_inside_polygon(&point(0.5, -0.5), &square));
+        assert!(!is_inside_polygon(&point(1.5, 0.0), &square));
+    }
+
+    #[test]
+    fn segments_crossing() {
+        let (a, b) = (point(1.0, -1.0), point(1.0, 1.0));
+        assert_near(
+            segments_intersection(&point(0.0, 0.0), &point(4.0, 0.0), &a, &b),
+            0.25,
+        );
+        assert_eq!(
+            segments_intersection(&point(0.0, 2.0), &point(4.0, 2.0), &a, &b),
+            None
+        );
+        // parallel
+        assert_eq!(
+            segments_intersection(&point(0.0, -1.0), &point(0.0, 1.0), &a, &b),
+            None
+        );
     }
 }
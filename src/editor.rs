@@ -0,0 +1,259 @@
+use egui::plot::PlotPoint;
+
+use crate::{
+    level::{Level, LevelObstacle, OBSTACLE_SIDES},
+    plotter::{distance, ENTITY_AMPLITUDE},
+};
+
+/// what pressing the board does in the editor
+#[derive(PartialEq, Clone, Copy)]
+pub enum EditorTool {
+    Player,   // moves the player
+    Enemy,    // adds an enemy
+    Obstacle, // adds an obstacle
+    Delete,   // removes an enemy or an obstacle
+}
+
+impl EditorTool {
+    pub const ALL: [EditorTool; 4] = [
+        EditorTool::Player,
+        EditorTool::Enemy,
+        EditorTool::Obstacle,
+        EditorTool::Delete,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            EditorTool::Player => "player",
+            EditorTool::Enemy => "enemy",
+            EditorTool::Obstacle => "obstacle",
+            EditorTool::Delete => "delete",
+        }
+    }
+}
+
+/// the part of the level which follows the pointer while dragging
+#[derive(Clone, Copy)]
+enum Grab {
+    Player,
+    Enemy(usize),
+    Obstacle(usize),
+    ObstacleRim(usize), // resizes the obstacle
+}
+
+pub struct LevelEditor {
+    pub level: Level,
+    pub tool: EditorTool,
+    pub save_path: String,
+    grabbed: Option<(Grab, [f64; 2])>, // (grab, offset from the pointer to the grabbed entity)
+}
+
+const SNAP_STEP: f64 = 0.5; // hand-authored levels have round positions
+const RIM_WIDTH: f64 = 0.5;
+const NEW_OBSTACLE_RADIUS: f64 = 3.0;
+const OBSTACLE_RADIUS_RANGE: (f64, f64) = (0.5, 15.0);
+
+fn snap(value: f64) -> f64 {
+    (value / SNAP_STEP).round() * SNAP_STEP
+}
+
+impl LevelEditor {
+    pub fn new(level: Level, save_path: String) -> Self {
+        Self {
+            level,
+            tool: EditorTool::Player,
+            save_path,
+            grabbed: None,
+        }
+    }
+
+    /// the entity under `point`, the ones drawn on top first
+    fn entity_at(&self, point: &PlotPoint) -> Option<Grab> {
+        if distance(&self.level.player.into(), point) <= ENTITY_AMPLITUDE {
+            return Some(Grab::Player);
+        }
+        if let Some(ennemy_id) = (0..self.level.enemies.len())
+            .rev()
+            .find(|&id| distance(&self.level.enemies[id].into(), point) <= ENTITY_AMPLITUDE)
+        {
+            return Some(Grab::Enemy(ennemy_id));
+        }
+        self.level
+            .obstacles
+            .iter()
+            .enumerate()
+            .rev()
+            .find_map(|(id, obstacle)| {
+                let from_center = distance(&obstacle.position.into(), point);
+                match (from_center - obstacle.radius).abs() <= RIM_WIDTH {
+                    true => Some(Grab::ObstacleRim(id)),
+                    false => (from_center < obstacle.radius).then_some(Grab::Obstacle(id)),
+                }
+            })
+    }
+
+    /// the pointer is pressed on the board: grab what is under it, or use the tool
+    pub fn press(&mut self, point: PlotPoint) {
+        let grab = self.entity_at(&point);
+        if self.tool == EditorTool::Delete {
+            match grab {
+                Some(Grab::Enemy(ennemy_id)) => {
+                    self.level.enemies.remove(ennemy_id);
+                }
+                Some(Grab::Obstacle(obstacle_id) | Grab::ObstacleRim(obstacle_id)) => {
+                    self.level.obstacles.remove(obstacle_id);
+                }
+                Some(Grab::Player) | None => {} // a level always has a player
+            }
+            return;
+        }
+
+        let grab = grab.unwrap_or_else(|| {
+            let position = [snap(point.x), snap(point.y)];
+            match self.tool {
+                EditorTool::Player => {
+                    self.level.player = position;
+                    Grab::Player
+                }
+                EditorTool::Enemy => {
+                    self.level.enemies.push(position);
+                    Grab::Enemy(self.level.enemies.len() - 1)
+                }
+                _ => {
+                    self.level.obstacles.push(LevelObstacle {
+                        position,
+                        radius: NEW_OBSTACLE_RADIUS,
+                        sides: OBSTACLE_SIDES,
+                    });
+                    Grab::Obstacle(self.level.obstacles.len() - 1)
+                }
+            }
+        });
+        let [x, y] = self.grabbed_position(grab);
+        self.grabbed = Some((grab, [x - point.x, y - point.y]));
+    }
+
+    /// the pointer moves while pressed: move or resize what has been grabbed
+    pub fn drag(&mut self, point: PlotPoint) {
+        let Some((grab, [offset_x, offset_y])) = self.grabbed else {
+            return;
+        };
+        let position = [snap(point.x + offset_x), snap(point.y + offset_y)];
+        match grab {
+            Grab::Player => self.level.player = position,
+            Grab::Enemy(ennemy_id) => self.level.enemies[ennemy_id] = position,
+            Grab::Obstacle(obstacle_id) => self.level.obstacles[obstacle_id].position = position,
+            Grab::ObstacleRim(obstacle_id) => {
+                let obstacle = &mut self.level.obstacles[obstacle_id];
+                let (min_radius, max_radius) = OBSTACLE_RADIUS_RANGE;
+                obstacle.radius =
+                    snap(distance(&obstacle.position.into(), &point)).clamp(min_radius, max_radius);
+            }
+        }
+    }
+
+    pub fn release(&mut self) {
+        self.grabbed = None;
+    }
+
+    fn grabbed_position(&self, grab: Grab) -> [f64; 2] {
+        match grab {
+            Grab::Player => self.level.player,
+            Grab::Enemy(ennemy_id) => self.level.enemies[ennemy_id],
+            Grab::Obstacle(obstacle_id) | Grab::ObstacleRim(obstacle_id) => {
+                self.level.obstacles[obstacle_id].position
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn editor() -> LevelEditor {
+        let level = toml::from_str(
+            r#"
+            player = [-20, 0]
+            enemies = [[15, 10]]
+            obstacles = [{ position = [0, 0], radius = 4, sides = 6 }]
+            "#,
+        )
+        .unwrap();
+        LevelEditor::new(level, String::new())
+    }
+
+    #[test]
+    fn placing() {
+        let mut editor = editor();
+        editor.tool = EditorTool::Enemy;
+        editor.press(PlotPoint::new(10.2, -7.9));
+        editor.release();
+        assert_eq!(editor.level.enemies, [[15.0, 10.0], [10.0, -8.0]]);
+
+        editor.tool = EditorTool::Obstacle;
+        editor.press(PlotPoint::new(-10.0, 12.0));
+        editor.release();
+        let obstacle = &editor.level.obstacles[1];
+        assert_eq!(obstacle.position, [-10.0, 12.0]);
+        assert_eq!(obstacle.radius, NEW_OBSTACLE_RADIUS);
+
+        editor.tool = EditorTool::Player;
+        editor.press(PlotPoint::new(-15.0, 5.0));
+        editor.release();
+        assert_eq!(editor.level.player, [-15.0, 5.0]);
+
+        // pressing an entity grabs it instead of using the tool
+        editor.tool = EditorTool::Enemy;
+        editor.press(PlotPoint::new(15.5, 10.0));
+        editor.release();
+        assert_eq!(editor.level.enemies.len(), 2);
+    }
+
+    #[test]
+    fn dragging_to_move() {
+        let mut editor = editor();
+        // the obstacle keeps its offset to the pointer
+        editor.press(PlotPoint::new(1.0, 1.0));
+        editor.drag(PlotPoint::new(6.0, -4.0));
+        editor.release();
+        assert_eq!(editor.level.obstacles[0].position, [5.0, -5.0]);
+        assert_eq!(editor.level.obstacles[0].radius, 4.0);
+
+        editor.press(PlotPoint::new(15.0, 10.0));
+        editor.drag(PlotPoint::new(12.3, 8.0));
+        editor.release();
+        assert_eq!(editor.level.enemies, [[12.5, 8.0]]);
+
+        // nothing follows the pointer once released
+        editor.drag(PlotPoint::new(0.0, 20.0));
+        assert_eq!(editor.level.enemies, [[12.5, 8.0]]);
+    }
+
+    #[test]
+    fn dragging_to_resize() {
+        let mut editor = editor();
+        editor.press(PlotPoint::new(4.2, 0.0)); // on the rim
+        editor.drag(PlotPoint::new(0.0, 6.1));
+        assert_eq!(editor.level.obstacles[0].radius, 6.0);
+        assert_eq!(editor.level.obstacles[0].position, [0.0, 0.0]);
+
+        editor.drag(PlotPoint::new(0.0, 0.0));
+        assert_eq!(editor.level.obstacles[0].radius, OBSTACLE_RADIUS_RANGE.0);
+        editor.drag(PlotPoint::new(40.0, 0.0));
+        assert_eq!(editor.level.obstacles[0].radius, OBSTACLE_RADIUS_RANGE.1);
+    }
+
+    #[test]
+    fn deleting() {
+        let mut editor = editor();
+        editor.tool = EditorTool::Delete;
+        editor.press(PlotPoint::new(-20.0, 0.0)); // a level always has a player
+        editor.press(PlotPoint::new(30.0, 30.0)); // nothing there
+        editor.press(PlotPoint::new(15.0, 10.5));
+        editor.press(PlotPoint::new(3.9, 0.0));
+        assert_eq!(editor.level.player, [-20.0, 0.0]);
+        assert!(editor.level.enemies.is_empty());
+        assert!(editor.level.obstacles.is_empty());
+    }
+}
@@ -1,123 +1,1754 @@
-use std::f64::consts::{E, LN_2, PI, SQRT_2, TAU};
+use std::{
+    f64::consts::{E, LN_10, LN_2, PI, SQRT_2, TAU},
+    fmt,
+    ops::Range,
+};
 
-use evalexpr::{eval_number, EvalexprError};
+use evalexpr::{
+    build_operator_tree, Context, EvalexprError, EvalexprResult, Node, Operator, Value,
+};
+
+/// a variable the player can use in its equation
+#[derive(Debug, PartialEq)]
+pub struct Variable {
+    pub name: &'static str,
+    aliases: &'static [&'static str], // what the player may type, lowercase
+    evaluable: &'static str,          // the identifier evalexpr knows it by
+}
+
+pub const X: Variable = Variable {
+    name: "x",
+    aliases: &["x"],
+    evaluable: "x",
+};
+pub const Y: Variable = Variable {
+    name: "y",
+    aliases: &["y"],
+    evaluable: "y",
+};
+pub const Y_PRIME: Variable = Variable {
+    name: "y'",
+    aliases: &["y'"],
+    evaluable: "y_prime",
+};
+pub const T: Variable = Variable {
+    name: "t",
+    aliases: &["t"],
+    evaluable: "t",
+};
+pub const THETA: Variable = Variable {
+    name: "θ",
+    aliases: &["theta", "t"],
+    evaluable: "theta",
+};
+
+/// variables of a plain function f(x)
+const FUNCTION_VARIABLES: &[Variable] = &[X];
+/// variables of `y' = f(x, y)`
+pub const FIRST_ORDER_ODE_VARIABLES: &[Variable] = &[X, Y];
+/// variables of `y'' = f(x, y, y')`
+pub const SECOND_ORDER_ODE_VARIABLES: &[Variable] = &[X, Y, Y_PRIME];
+/// variables of the `x(t)` and `y(t)` of a parametric curve
+pub const PARAMETRIC_VARIABLES: &[Variable] = &[T];
+/// variables of the `r(θ)` of a polar curve
+pub const POLAR_VARIABLES: &[Variable] = &[THETA];
+
+/// x interval on which an expression is sampled to detect constant lines,
+/// it covers the largest boards (100 units wide) wherever the player stands
+const CONSTANT_CHECK_INTERVAL: (f64, f64) = (-100.0, 100.0);
+// dense enough for the narrow pieces of lines, like `bump(x, 1, 1.2)`
+const CONSTANT_CHECK_SAMPLES: usize = 20_000;
+
+/// math constants the player can type, with their value
+pub const CONSTANTS: [(&str, f64); 5] = [
+    ("pi", PI),
+    ("tau", TAU),
+    ("e", E),
+    ("sqrttwo", SQRT_2),
+    ("lntwo", LN_2),
+];
+
+/// how a function of the registry is evaluated
+#[derive(Debug)]
+pub enum FunctionKind {
+    /// a function shipped with evalexpr, with its evalexpr name
+    Builtin(&'static str),
+    /// a function implemented here
+    Custom(fn(&[f64]) -> f64),
+}
+
+/// how many arguments a function takes
+#[derive(Debug, Clone, Copy)]
+pub enum Arity {
+    Exactly(usize),
+    AtLeast(usize), // the functions of a list, like min
+}
+
+impl Arity {
+    fn accepts(self, arguments: usize) -> bool {
+        match self {
+            Arity::Exactly(expected) => arguments == expected,
+            Arity::AtLeast(expected) => arguments >= expected,
+        }
+    }
+}
+
+impl fmt::Display for Arity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Arity::Exactly(1) => write!(f, "1 argument"),
+            Arity::Exactly(expected) => write!(f, "{expected} arguments"),
+            Arity::AtLeast(expected) => write!(f, "at least {expected} arguments"),
+        }
+    }
+}
+
+/// a function the player can use in its equation
+#[derive(Debug)]
+pub struct Function {
+    pub name: &'static str,
+    pub signature: &'static str,
+    pub description: &'static str,
+    pub arity: Arity,
+    pub kind: FunctionKind,
+}
+
+impl PartialEq for Function {
+    fn eq(&self, other: &Self) -> bool {
+        self.name == other.name
+    }
+}
+
+impl Function {
+    /// the identifier under which evalexpr knows this function
+    fn evaluable_name(&self) -> &'static str {
+        match self.kind {
+            FunctionKind::Builtin(evaluable) => evaluable,
+            // resolved by the evaluation context before evalexpr looks at its builtins
+            FunctionKind::Custom(_) => self.name,
+        }
+    }
+}
+
+const fn builtin(
+    name: &'static str,
+    signature: &'static str,
+    description: &'static str,
+    evaluable: &'static str,
+    arity: Arity,
+) -> Function {
+    Function {
+        name,
+        signature,
+        description,
+        arity,
+        kind: FunctionKind::Builtin(evaluable),
+    }
+}
+
+const fn custom(
+    name: &'static str,
+    signature: &'static str,
+    description: &'static str,
+    function: fn(&[f64]) -> f64,
+    arity: usize,
+) -> Function {
+    Function {
+        name,
+        signature,
+        description,
+        arity: Arity::Exactly(arity),
+        kind: FunctionKind::Custom(function),
+    }
+}
+
+/// every function the player can type, anything else is rejected
+pub static FUNCTIONS: [Function; 35] = [
+    // trigonometry
+    builtin("sin", "sin(x)", "sine", "math::sin", Arity::Exactly(1)),
+    builtin("cos", "cos(x)", "cosine", "math::cos", Arity::Exactly(1)),
+    builtin("tan", "tan(x)", "tangent", "math::tan", Arity::Exactly(1)),
+    builtin(
+        "asin",
+        "asin(x)",
+        "inverse sine",
+        "math::asin",
+        Arity::Exactly(1),
+    ),
+    builtin(
+        "acos",
+        "acos(x)",
+        "inverse cosine",
+        "math::acos",
+        Arity::Exactly(1),
+    ),
+    builtin(
+        "atan",
+        "atan(x)",
+        "inverse tangent",
+        "math::atan",
+        Arity::Exactly(1),
+    ),
+    builtin(
+        "atan2",
+        "atan2(y, x)",
+        "angle of the point (x, y)",
+        "math::atan2",
+        Arity::Exactly(2),
+    ),
+    builtin(
+        "sinh",
+        "sinh(x)",
+        "hyperbolic sine",
+        "math::sinh",
+        Arity::Exactly(1),
+    ),
+    builtin(
+        "cosh",
+        "cosh(x)",
+        "hyperbolic cosine",
+        "math::cosh",
+        Arity::Exactly(1),
+    ),
+    builtin(
+        "tanh",
+        "tanh(x)",
+        "hyperbolic tangent",
+        "math::tanh",
+        Arity::Exactly(1),
+    ),
+    builtin(
+        "asinh",
+        "asinh(x)",
+        "inverse hyperbolic sine",
+        "math::asinh",
+        Arity::Exactly(1),
+    ),
+    builtin(
+        "acosh",
+        "acosh(x)",
+        "inverse hyperbolic cosine",
+        "math::acosh",
+        Arity::Exactly(1),
+    ),
+    builtin(
+        "atanh",
+        "atanh(x)",
+        "inverse hyperbolic tangent",
+        "math::atanh",
+        Arity::Exactly(1),
+    ),
+    // exponentials and powers
+    builtin(
+        "exp",
+        "exp(x)",
+        "e to the power of x",
+        "math::exp",
+        Arity::Exactly(1),
+    ),
+    builtin(
+        "exp2",
+        "exp2(x)",
+        "2 to the power of x",
+        "math::exp2",
+        Arity::Exactly(1),
+    ),
+    builtin(
+        "ln",
+        "ln(x)",
+        "natural logarithm",
+        "math::ln",
+        Arity::Exactly(1),
+    ),
+    builtin(
+        "log",
+        "log(x, b)",
+        "logarithm of x in base b",
+        "math::log",
+        Arity::Exactly(2),
+    ),
+    builtin(
+        "log2",
+        "log2(x)",
+        "base 2 logarithm",
+        "math::log2",
+        Arity::Exactly(1),
+    ),
+    builtin(
+        "log10",
+        "log10(x)",
+        "base 10 logarithm",
+        "math::log10",
+        Arity::Exactly(1),
+    ),
+    builtin(
+        "pow",
+        "pow(x, p)",
+        "x to the power of p, same as x^p",
+        "math::pow",
+        Arity::Exactly(2),
+    ),
+    builtin(
+        "sqrt",
+        "sqrt(x)",
+        "square root",
+        "math::sqrt",
+        Arity::Exactly(1),
+    ),
+    builtin(
+        "cbrt",
+        "cbrt(x)",
+        "cube root",
+        "math::cbrt",
+        Arity::Exactly(1),
+    ),
+    builtin(
+        "hypot",
+        "hypot(x, y)",
+        "length of the vector (x, y)",
+        "math::hypot",
+        Arity::Exactly(2),
+    ),
+    // rounding and pieces
+    builtin(
+        "abs",
+        "abs(x)",
+        "absolute value, same as |x|",
+        "math::abs",
+        Arity::Exactly(1),
+    ),
+    builtin(
+        "floor",
+        "floor(x)",
+        "largest integer below x",
+        "floor",
+        Arity::Exactly(1),
+    ),
+    builtin(
+        "ceil",
+        "ceil(x)",
+        "smallest integer above x",
+        "ceil",
+        Arity::Exactly(1),
+    ),
+    builtin(
+        "round",
+        "round(x)",
+        "nearest integer",
+        "round",
+        Arity::Exactly(1),
+    ),
+    builtin(
+        "min",
+        "min(a, b, ...)",
+        "smallest of its arguments",
+        "min",
+        Arity::AtLeast(2),
+    ),
+    builtin(
+        "max",
+        "max(a, b, ...)",
+        "largest of its arguments",
+        "max",
+        Arity::AtLeast(2),
+    ),
+    custom(
+        "sign",
+        "sign(x)",
+        "-1 when x < 0, 0 when x = 0, 1 when x > 0",
+        sign,
+        1,
+    ),
+    custom(
+        "mod",
+        "mod(x, m)",
+        "remainder of x divided by m, always positive",
+        modulo,
+        2,
+    ),
+    custom(
+        "clamp",
+        "clamp(x, a, b)",
+        "x restricted to the interval [a, b]",
+        clamp,
+        3,
+    ),
+    // graphwar helpers
+    custom(
+        "step",
+        "step(x)",
+        "0 when x < 0, 1 otherwise: turns a line on at x = 0",
+        step,
+        1,
+    ),
+    custom(
+        "sigmoid",
+        "sigmoid(x)",
+        "smooth step from 0 to 1 around x = 0",
+        sigmoid,
+        1,
+    ),
+    custom(
+        "bump",
+        "bump(x, a, b)",
+        "1 when x is in [a, b], 0 elsewhere: restricts a line to [a, b]",
+        bump,
+        3,
+    ),
+];
+
+fn sign(args: &[f64]) -> f64 {
+    match args[0] {
+        0.0 => 0.0,
+        x => x.signum(),
+    }
+}
+
+fn modulo(args: &[f64]) -> f64 {
+    args[0].rem_euclid(args[1])
+}
+
+fn clamp(args: &[f64]) -> f64 {
+    // the bounds can be given in any order
+    let (low, high) = (args[1].min(args[2]), args[1].max(args[2]));
+    args[0].max(low).min(high)
+}
+
+fn step(args: &[f64]) -> f64 {
+    match args[0] < 0.0 {
+        true => 0.0,
+        false => 1.0,
+    }
+}
+
+fn sigmoid(args: &[f64]) -> f64 {
+    1.0 / (1.0 + (-args[0]).exp())
+}
+
+fn bump(args: &[f64]) -> f64 {
+    match args[1] <= args[0] && args[0] <= args[2] {
+        true => 1.0,
+        false => 0.0,
+    }
+}
+
+/// unicode glyphs (as found in notes or chat) the player can type, with their plain text form
+pub const UNICODE_SYMBOLS: [(char, &str); 9] = [
+    ('π', "pi"),
+    ('τ', "tau"),
+    ('θ', "theta"),
+    ('√', "sqrt"),
+    ('²', "^2"),
+    ('³', "^3"),
+    ('×', "*"),
+    ('÷', "/"),
+    ('−', "-"),
+];
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Number(f64),
+    Variable(&'static Variable),
+    Constant(f64),
+    Function(&'static str), // evalexpr name of the function
+    Operator(char),
+    LeftParen,
+    RightParen,
+    Comma,
+}
+
+impl Token {
+    /// whether the token can be the last one of an operand, e.g. `2`, `x` or `)`
+    fn ends_operand(&self) -> bool {
+        matches!(
+            self,
+            Token::Number(_) | Token::Variable(_) | Token::Constant(_) | Token::RightParen
+        )
+    }
+
+    /// whether the token can be the first one of an operand, e.g. `2`, `sin` or `(`
+    fn starts_operand(&self) -> bool {
+        matches!(
+            self,
+            Token::Number(_)
+                | Token::Variable(_)
+                | Token::Constant(_)
+                | Token::Function(_)
+                | Token::LeftParen
+        )
+    }
+}
+
+/// evaluation context that only knows about the bound variables and the custom functions,
+/// other functions are left to the evalexpr builtins
+struct VariableContext {
+    variables: &'static [Variable],
+    values: Vec<Value>,
+}
+
+impl Context for VariableContext {
+    fn get_value(&self, identifier: &str) -> Option<&Value> {
+        self.variables
+            .iter()
+            .position(|variable| variable.evaluable == identifier)
+            .and_then(|i| self.values.get(i))
+    }
+
+    fn call_function(&self, identifier: &str, argument: &Value) -> EvalexprResult<Value> {
+        let custom = FUNCTIONS
+            .iter()
+            .find(|function| function.evaluable_name() == identifier)
+            .and_then(|function| match function.kind {
+                FunctionKind::Custom(implementation) => Some((function, implementation)),
+                FunctionKind::Builtin(_) => None,
+            });
+        let Some((function, implementation)) = custom else {
+            return Err(EvalexprError::FunctionIdentifierNotFound(
+                identifier.to_string(),
+            ));
+        };
+
+        // the arguments are checked when parsing, this is only a safety net
+        let wrong_arguments = || {
+            EvalexprError::CustomMessage(format!(
+                "{} takes {} (numbers)",
+                function.name, function.arity
+            ))
+        };
+        let arguments = match argument {
+            Value::Tuple(values) => values
+                .iter()
+                .map(Value::as_number)
+                .collect::<EvalexprResult<Vec<_>>>(),
+            value => value.as_number().map(|value| vec![value]),
+        }
+        .map_err(|_| wrong_arguments())?;
+        if !function.arity.accepts(arguments.len()) {
+            return Err(wrong_arguments());
+        }
+        Ok(Value::Float(implementation(&arguments)))
+    }
+
+    fn are_builtin_functions_disabled(&self) -> bool {
+        false
+    }
+
+    fn set_builtin_functions_disabled(&mut self, _disabled: bool) -> EvalexprResult<()> {
+        Err(EvalexprError::ContextNotMutable)
+    }
+}
+
+/// error raised when building a `MathExpression`, `span` is the character range
+/// of the offending part of the raw input when it is known
+#[derive(Debug)]
+pub struct ExpressionError {
+    pub error: EvalexprError,
+    pub span: Option<Range<usize>>,
+}
+
+impl ExpressionError {
+    fn at(span: Range<usize>, message: String) -> Self {
+        Self {
+            error: EvalexprError::CustomMessage(message),
+            span: Some(span),
+        }
+    }
+}
+
+impl From<EvalexprError> for ExpressionError {
+    fn from(error: EvalexprError) -> Self {
+        Self { error, span: None }
+    }
+}
 
 pub struct MathExpression {
-    expr: String,
+    /// the expression is parsed only once, then evaluated for each sample
+    tree: Node,
+    variables: &'static [Variable],
 }
 
 impl MathExpression {
-    pub fn new(raw_expr: &str) -> Result<Self, EvalexprError> {
-        let evaluable = Self::convert_to_evaluable(raw_expr.to_string());
-        eval_number(
-            Self::replace_with_value(&evaluable, 0.0 /* no matter which value I put here */)
-                .as_str(),
-        )?;
-
-        // TODO: won't catch thing like: exp(1) for example
-        if !evaluable.contains('x') {
+    /// a plain function f(x), which is not allowed to be constant
+    pub fn new(raw_expr: &str) -> Result<Self, ExpressionError> {
+        let valid_expr = Self::with_variables(raw_expr, FUNCTION_VARIABLES)?;
+        valid_expr.check_depends_on_variable()?;
+
+        Ok(valid_expr)
+    }
+
+    /// an expression of several variables, given in the order `compute_with` takes their values
+    pub fn with_variables(
+        raw_expr: &str,
+        variables: &'static [Variable],
+    ) -> Result<Self, ExpressionError> {
+        let (normalized, origins) = Self::normalize(raw_expr);
+        let to_raw_span = |span: Range<usize>| match span.is_empty() {
+            true => origins[span.start]..origins[span.start],
+            false => origins[span.start]..origins[span.end - 1] + 1,
+        };
+        let tokens = Self::tokenize(&normalized, variables)
+            .map_err(|why| ExpressionError {
+                span: why.span.map(to_raw_span),
+                ..why
+            })?
+            .into_iter()
+            .map(|(token, span)| (token, to_raw_span(span)))
+            .collect::<Vec<_>>();
+        Self::check_syntax(&tokens, raw_expr.chars().count())?;
+
+        let evaluable = Self::convert_to_evaluable(&tokens);
+        let valid_expr = Self {
+            tree: build_operator_tree(&evaluable)?,
+            variables,
+        };
+        valid_expr
+            .compute_with(&vec![0.0 /* no matter which value I put here */; variables.len()])?;
+
+        Ok(valid_expr)
+    }
+
+    /// make sure the value of the expression really depends on x, things like `exp(1)`,
+    /// `x-x`, `0*x` or `sin(x)^0` are thus rejected. x must appear in the simplified
+    /// expression, then the expression is sampled across the board for the cases
+    /// simplifications miss, like `x^2 - x*x`
+    fn check_depends_on_variable(&self) -> Result<(), EvalexprError> {
+        let constant_error = || {
+            EvalexprError::CustomMessage(
+                "this line is constant (its value does not depend on x), which is forbidden 😊"
+                    .to_string(),
+            )
+        };
+        if let Ok(symbolic) = Symbolic::from_node(&self.tree, self.variables) {
+            if !symbolic.simplify().depends_on_variable(&self.variables[0]) {
+                return Err(constant_error());
+            }
+        }
+
+        let (from, to) = CONSTANT_CHECK_INTERVAL;
+        let step = (to - from) / CONSTANT_CHECK_SAMPLES as f64;
+        // the offset keeps the samples away from integers and multiples of pi,
+        // otherwise periodic lines like `sin(pi*x)` would look constant
+        let mut samples = (0..CONSTANT_CHECK_SAMPLES)
+            .map(|k| from + (k as f64 + 0.377) * step)
+            .filter_map(|x| self.compute(x).ok())
+            .filter(|y| y.is_finite());
+
+        let Some(first) = samples.next() else {
             return Err(EvalexprError::CustomMessage(
-                "this line is constant, which is forbidden 😊".to_string(),
+                "this line is not defined anywhere on the board 🤔".to_string(),
             ));
+        };
+        // stops at the first sample that differs, the whole interval is only
+        // sampled for lines that look constant
+        let varies = samples.any(|y| (y - first).abs() > 1e-9 * y.abs().max(first.abs()).max(1.0));
+        match varies {
+            true => Ok(()),
+            false => Err(constant_error()),
         }
+    }
 
-        let valid_expr = Self { expr: evaluable };
-        Ok(valid_expr)
+    /// symbolic derivative of the expression with respect to its first variable,
+    /// with its trivial terms simplified
+    pub fn derivative(&self) -> Result<MathExpression, EvalexprError> {
+        let derivative = Symbolic::from_node(&self.tree, self.variables)?
+            .derive(&self.variables[0])?
+            .simplify();
+        Ok(Self {
+            tree: build_operator_tree(&derivative.write(false))?,
+            variables: self.variables,
+        })
     }
 
+    /// evaluate an expression of a single variable
     pub fn compute(&self, x: f64) -> Result<f64, EvalexprError> {
-        eval_number(Self::replace_with_value(&self.expr, x).as_str())
+        self.compute_with(&[x])
     }
 
-    fn replace_with_value(expr: &str, x: f64) -> String {
-        expr.replace("exp", "ewp")
-            .replace('x', format!("({x})").as_str())
-            .replace("ewp", "exp")
+    /// evaluate the expression, `values` being in the same order as its variables
+    pub fn compute_with(&self, values: &[f64]) -> Result<f64, EvalexprError> {
+        self.tree.eval_number_with_context(&VariableContext {
+            variables: self.variables,
+            values: values.iter().map(|&value| Value::Float(value)).collect(),
+        })
     }
 
-    fn convert_to_evaluable(expr: String) -> String {
-        let mut math_keywords = vec![];
+    /// replace the unicode glyphs by their plain text form. Alongside the normalized
+    /// expression, the raw char index of each normalized char is returned so that
+    /// spans can be reported on what the player actually typed
+    fn normalize(raw_expr: &str) -> (String, Vec<usize>) {
+        let mut normalized = String::new();
+        let mut origins = vec![];
+        for (i, ch) in raw_expr.chars().enumerate() {
+            match UNICODE_SYMBOLS.iter().find(|(glyph, _)| *glyph == ch) {
+                Some((_, plain)) => {
+                    // surrounding spaces prevent the plain form from merging with its neighbours
+                    let plain = format!(" {plain} ");
+                    origins.extend(std::iter::repeat_n(i, plain.chars().count()));
+                    normalized.push_str(&plain);
+                }
+                None => {
+                    origins.push(i);
+                    normalized.push(ch);
+                }
+            }
+        }
+        origins.push(raw_expr.chars().count()); // the end of the expression
+        (normalized, origins)
+    }
 
-        let (mut current_word, mut current_word_start) = (String::new(), 0);
-        for (i, ch) in expr.chars().enumerate() {
-            match ch.is_ascii_alphabetic() {
-                true => match current_word == *"" {
-                    true => {
-                        current_word = format!("{ch}");
-                        current_word_start = i
+    /// split the raw player input into tokens, rejecting anything that is not
+    /// a number, an operator, the variable, a known constant or a known function.
+    ///
+    /// The usual handwritten syntax is accepted too: juxtaposition is a multiplication
+    /// (`2x`, `3sin(x)`, `(x+1)(x-1)`, `xpi`), `**` is a power, `|x|` is `abs(x)`
+    /// and `1e3` is a number in scientific notation
+    fn tokenize(
+        raw_expr: &str,
+        variables: &'static [Variable],
+    ) -> Result<Vec<(Token, Range<usize>)>, ExpressionError> {
+        let mut tokens = vec![];
+        let mut open_bars: Vec<Range<usize>> = vec![];
+        let mut chars = raw_expr.chars().enumerate().peekable();
+
+        while let Some(&(start, ch)) = chars.peek() {
+            match ch {
+                _ if ch.is_whitespace() => {
+                    chars.next();
+                }
+                '0'..='9' | '.' => {
+                    let mut literal = String::new();
+                    while let Some(&(_, digit)) = chars.peek() {
+                        if !(digit.is_ascii_digit() || digit == '.') {
+                            break;
+                        }
+                        literal.push(digit);
+                        chars.next();
                     }
-                    false => current_word.push(ch),
-                },
-                false => {
-                    match current_word.as_str() {
-                        "x" | "" => (),
-                        _ => math_keywords.push((current_word_start, i - 1, current_word.clone())),
+                    // scientific notation: `1e3` is 1000, not 1*e*3
+                    let mut lookahead = chars.clone();
+                    if lookahead
+                        .next_if(|&(_, ch)| matches!(ch, 'e' | 'E'))
+                        .is_some()
+                    {
+                        let sign = lookahead.next_if(|&(_, ch)| matches!(ch, '+' | '-'));
+                        if lookahead.peek().is_some_and(|(_, ch)| ch.is_ascii_digit()) {
+                            literal.push('e');
+                            literal.extend(sign.map(|(_, sign)| sign));
+                            while let Some((_, digit)) =
+                                lookahead.next_if(|(_, ch)| ch.is_ascii_digit())
+                            {
+                                literal.push(digit);
+                            }
+                            chars = lookahead;
+                        }
+                    }
+                    let span = start..start + literal.len();
+                    let value = literal.parse::<f64>().map_err(|_| {
+                        ExpressionError::at(
+                            span.clone(),
+                            format!("\"{literal}\" is not a valid number"),
+                        )
+                    })?;
+                    Self::push_token(&mut tokens, Token::Number(value), span);
+                }
+                _ if ch.is_ascii_alphabetic() => {
+                    let mut word = String::new();
+                    while let Some(&(_, letter)) = chars.peek() {
+                        // a trailing quote is part of the name, as in y'
+                        if !(letter.is_ascii_alphanumeric() || letter == '_' || letter == '\'') {
+                            break;
+                        }
+                        word.push(letter);
+                        chars.next();
+                    }
+                    let span = start..start + word.len();
+                    let identifiers =
+                        Self::split_identifier(&word, variables).ok_or_else(|| {
+                            ExpressionError::at(
+                                span.clone(),
+                                format!("\"{word}\" is neither a known function nor a constant"),
+                            )
+                        })?;
+                    for (token, relative_span) in identifiers {
+                        Self::push_token(
+                            &mut tokens,
+                            token,
+                            start + relative_span.start..start + relative_span.end,
+                        );
                     }
-                    current_word = String::new()
+                }
+                '*' => {
+                    chars.next();
+                    match chars.peek() {
+                        // python-like power
+                        Some(&(_, '*')) => {
+                            chars.next();
+                            Self::push_token(&mut tokens, Token::Operator('^'), start..start + 2);
+                        }
+                        _ => Self::push_token(&mut tokens, Token::Operator('*'), start..start + 1),
+                    }
+                }
+                '|' => {
+                    chars.next();
+                    let span = start..start + 1;
+                    let closes_bar = !open_bars.is_empty()
+                        && tokens.last().is_some_and(|(token, _)| token.ends_operand());
+                    if closes_bar {
+                        open_bars.pop();
+                        Self::push_token(&mut tokens, Token::RightParen, span);
+                    } else {
+                        open_bars.push(span.clone());
+                        Self::push_token(&mut tokens, Token::Function("math::abs"), span.clone());
+                        Self::push_token(&mut tokens, Token::LeftParen, span);
+                    }
+                }
+                // evalexpr has no unary '+', and it changes nothing anyway
+                '+' if !tokens.last().is_some_and(|(token, _)| token.ends_operand()) => {
+                    chars.next();
+                }
+                '+' | '-' | '/' | '^' | '%' | '(' | ')' | ',' => {
+                    let token = match ch {
+                        '(' => Token::LeftParen,
+                        ')' => Token::RightParen,
+                        ',' => Token::Comma,
+                        op => Token::Operator(op),
+                    };
+                    Self::push_token(&mut tokens, token, start..start + 1);
+                    chars.next();
+                }
+                _ => {
+                    return Err(ExpressionError::at(
+                        start..start + 1,
+                        format!("unexpected symbol '{ch}' in the equation"),
+                    ))
                 }
             }
         }
-        match current_word.as_str() {
-            "x" | "" => (),
-            _ => math_keywords.push((current_word_start, expr.len() - 1, current_word.clone())),
-        }
-
-        let mut evaluable_expr = expr;
-        for (start_idx, end_idx, content) in math_keywords.iter().rev() {
-            let indexable = evaluable_expr.as_str();
-
-            match content.to_lowercase().as_str() {
-                // some math constant
-                "pi" => {
-                    evaluable_expr = format!(
-                        "{}{}{}",
-                        &indexable[..*start_idx],
-                        PI,
-                        &indexable[*end_idx + 1..]
-                    )
-                }
-                "tau" => {
-                    evaluable_expr = format!(
-                        "{}{}{}",
-                        &indexable[..*start_idx],
-                        TAU,
-                        &indexable[*end_idx + 1..]
-                    )
-                }
-                "sqrttwo" => {
-                    evaluable_expr = format!(
-                        "{}{}{}",
-                        &indexable[..*start_idx],
-                        SQRT_2,
-                        &indexable[*end_idx + 1..]
-                    )
-                }
-                "e" => {
-                    evaluable_expr = format!(
-                        "{}{}{}",
-                        &indexable[..*start_idx],
-                        E,
-                        &indexable[*end_idx + 1..]
-                    )
-                }
-                "lntwo" => {
-                    evaluable_expr = format!(
-                        "{}{}{}",
-                        &indexable[..*start_idx],
-                        LN_2,
-                        &indexable[*end_idx + 1..]
-                    )
-                }
-                // math function
-                _ => {
-                    evaluable_expr = format!(
-                        "{}math::{}",
-                        &indexable[..*start_idx],
-                        &indexable[*start_idx..]
-                    )
+
+        match open_bars.pop() {
+            Some(span) => Err(ExpressionError::at(
+                span,
+                "this absolute value bar is never closed".to_string(),
+            )),
+            None => Ok(tokens),
+        }
+    }
+
+    /// push a token, with a multiplication before it when it is juxtaposed to an operand
+    fn push_token(tokens: &mut Vec<(Token, Range<usize>)>, token: Token, span: Range<usize>) {
+        let is_juxtaposed = tokens
+            .last()
+            .is_some_and(|(previous, _)| previous.ends_operand())
+            && token.starts_operand();
+        if is_juxtaposed {
+            tokens.push((Token::Operator('*'), span.start..span.start));
+        }
+        tokens.push((token, span));
+    }
+
+    /// split a word into known identifiers and numbers, such that `xpi` is `x pi`
+    /// and `x2` is `x 2`. The spans returned are relative to the word.
+    fn split_identifier(
+        word: &str,
+        variables: &'static [Variable],
+    ) -> Option<Vec<(Token, Range<usize>)>> {
+        // the offsets from which the rest of the word can't be split. names that can be cut
+        // several ways (`log2` or `log` then `2`) would otherwise be tried again and again
+        let mut dead_ends = vec![false; word.len()];
+        Self::split_identifier_from(word, 0, variables, &mut dead_ends)
+    }
+
+    fn split_identifier_from(
+        word: &str,
+        start: usize,
+        variables: &'static [Variable],
+        dead_ends: &mut [bool],
+    ) -> Option<Vec<(Token, Range<usize>)>> {
+        if start == word.len() {
+            return Some(vec![]);
+        }
+        if dead_ends[start] {
+            return None;
+        }
+
+        let rest = &word[start..];
+        let mut split_after = |token: Token, len: usize| {
+            let mut identifiers = vec![(token, start..start + len)];
+            identifiers.extend(Self::split_identifier_from(
+                word,
+                start + len,
+                variables,
+                dead_ends,
+            )?);
+            Some(identifiers)
+        };
+        let mut digits_len = rest.chars().take_while(char::is_ascii_digit).count();
+        let split = match digits_len > 0 {
+            true => {
+                // scientific notation, as in `x2e3`
+                let exponent = &rest.as_bytes()[digits_len..];
+                if exponent.len() > 1
+                    && exponent[0].eq_ignore_ascii_case(&b'e')
+                    && exponent[1].is_ascii_digit()
+                {
+                    digits_len += 1 + exponent[1..]
+                        .iter()
+                        .take_while(|ch| ch.is_ascii_digit())
+                        .count();
+                }
+                let number = rest[..digits_len].parse().ok().map(Token::Number);
+                number.and_then(|number| split_after(number, digits_len))
+            }
+            // longest known prefix first, e.g. `exp` rather than `e` followed by `xp`
+            false => (1..=rest.len())
+                .rev()
+                .find_map(|len| split_after(Self::identify(&rest[..len], variables)?, len)),
+        };
+        if split.is_none() {
+            dead_ends[start] = true;
+        }
+        split
+    }
+
+    /// resolve an identifier to a variable, a constant or a whitelisted function
+    fn identify(word: &str, variables: &'static [Variable]) -> Option<Token> {
+        let word = word.to_lowercase();
+        if let Some(variable) = variables
+            .iter()
+            .find(|variable| variable.aliases.contains(&word.as_str()))
+        {
+            return Some(Token::Variable(variable));
+        }
+        if let Some((_, value)) = CONSTANTS.iter().find(|(name, _)| *name == word) {
+            return Some(Token::Constant(*value));
+        }
+        FUNCTIONS
+            .iter()
+            .find(|function| function.name == word)
+            .map(|function| Token::Function(function.evaluable_name()))
+    }
+
+    /// catch the common typing mistakes before evalexpr does, so that the faulty part
+    /// of the equation can be pointed at: unbalanced parentheses and missing operands
+    fn check_syntax(
+        tokens: &[(Token, Range<usize>)],
+        input_len: usize,
+    ) -> Result<(), ExpressionError> {
+        let ends_operand = |token: Option<&Token>| token.is_some_and(Token::ends_operand);
+        let starts_operand = |token: Option<&Token>| {
+            token.is_some_and(|token| token.starts_operand() || *token == Token::Operator('-'))
+        };
+
+        if tokens.is_empty() {
+            return Err(ExpressionError::at(
+                0..input_len,
+                "the equation is empty".to_string(),
+            ));
+        }
+
+        // (span, the function the parenthesis holds the arguments of, count of these arguments)
+        let mut open_parens = vec![];
+        for (i, (token, span)) in tokens.iter().enumerate() {
+            let previous = i.checked_sub(1).map(|i| &tokens[i].0);
+            let next = tokens.get(i + 1).map(|(token, _)| token);
+
+            match token {
+                Token::Operator(op) => {
+                    // a leading '-' is a sign, not a binary operator (leading '+' are dropped)
+                    let is_sign = *op == '-';
+                    if !is_sign && !ends_operand(previous) {
+                        return Err(ExpressionError::at(
+                            span.clone(),
+                            format!("missing operand before '{op}'"),
+                        ));
+                    }
+                    if !starts_operand(next) {
+                        return Err(ExpressionError::at(
+                            span.clone(),
+                            format!("missing operand after '{op}'"),
+                        ));
+                    }
+                }
+                Token::Comma => {
+                    let Some((_, Some(_), arguments)) = open_parens.last_mut() else {
+                        return Err(ExpressionError::at(
+                            span.clone(),
+                            "',' only separates the arguments of a function".to_string(),
+                        ));
+                    };
+                    *arguments += 1;
+                    if !ends_operand(previous) || !starts_operand(next) {
+                        return Err(ExpressionError::at(
+                            span.clone(),
+                            "missing function argument around ','".to_string(),
+                        ));
+                    }
+                }
+                Token::Function(evaluable) => {
+                    if !starts_operand(next) {
+                        return Err(ExpressionError::at(
+                            span.clone(),
+                            "this function is missing its argument".to_string(),
+                        ));
+                    }
+                    // without parentheses, as in `sin x`, the function takes the next operand
+                    if next != Some(&Token::LeftParen) {
+                        Self::check_arity(evaluable, span, 1)?;
+                    }
+                }
+                Token::LeftParen => {
+                    if next == Some(&Token::RightParen) {
+                        return Err(ExpressionError::at(
+                            span.start..tokens[i + 1].1.end,
+                            "nothing inside these parentheses".to_string(),
+                        ));
+                    }
+                    let call = match i.checked_sub(1).map(|i| &tokens[i]) {
+                        Some((Token::Function(evaluable), function_span)) => {
+                            Some((*evaluable, function_span.clone()))
+                        }
+                        _ => None,
+                    };
+                    open_parens.push((span.clone(), call, 1))
+                }
+                Token::RightParen => match open_parens.pop() {
+                    None => {
+                        return Err(ExpressionError::at(
+                            span.clone(),
+                            "this parenthesis is never opened".to_string(),
+                        ))
+                    }
+                    Some((_, Some((evaluable, function_span)), arguments)) => {
+                        Self::check_arity(evaluable, &function_span, arguments)?
+                    }
+                    Some((_, None, _)) => (),
+                },
+                Token::Number(_) | Token::Variable(_) | Token::Constant(_) => (),
+            }
+        }
+
+        match open_parens.pop() {
+            Some((span, ..)) => Err(ExpressionError::at(
+                span,
+                "this parenthesis is never closed".to_string(),
+            )),
+            None => Ok(()),
+        }
+    }
+
+    /// reject a call with the wrong number of arguments, pointing at the function name
+    fn check_arity(
+        evaluable: &str,
+        span: &Range<usize>,
+        arguments: usize,
+    ) -> Result<(), ExpressionError> {
+        let Some(function) = FUNCTIONS
+            .iter()
+            .find(|function| function.evaluable_name() == evaluable)
+        else {
+            return Ok(());
+        };
+        match function.arity.accepts(arguments) {
+            true => Ok(()),
+            false => Err(ExpressionError::at(
+                span.clone(),
+                format!(
+                    "{} takes {}, not {arguments}, see \"functions ❓\"",
+                    function.name, function.arity
+                ),
+            )),
+        }
+    }
+
+    /// write the tokens back as an expression evalexpr understands
+    fn convert_to_evaluable(tokens: &[(Token, Range<usize>)]) -> String {
+        tokens
+            .iter()
+            .map(|(token, _)| match token {
+                // numbers are always written as floats, otherwise evalexpr does integer divisions
+                Token::Number(value) | Token::Constant(value) => format!("{value:?}"),
+                Token::Variable(variable) => variable.evaluable.to_string(),
+                Token::Function(evaluable) => evaluable.to_string(),
+                Token::Operator(op) => op.to_string(),
+                Token::LeftParen => "(".to_string(),
+                Token::RightParen => ")".to_string(),
+                Token::Comma => ",".to_string(),
+            })
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+}
+
+impl fmt::Display for MathExpression {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match Symbolic::from_node(&self.tree, self.variables) {
+            Ok(symbolic) => write!(f, "{}", symbolic.simplify().write(true)),
+            Err(_) => write!(f, "{}", self.tree),
+        }
+    }
+}
+
+/// a math expression as a tree of operations, so that it can be derived and simplified
+#[derive(Debug, Clone, PartialEq)]
+enum Symbolic {
+    Number(f64),
+    Variable(&'static Variable),
+    Neg(Box<Symbolic>),
+    Add(Box<Symbolic>, Box<Symbolic>),
+    Sub(Box<Symbolic>, Box<Symbolic>),
+    Mul(Box<Symbolic>, Box<Symbolic>),
+    Div(Box<Symbolic>, Box<Symbolic>),
+    Mod(Box<Symbolic>, Box<Symbolic>),
+    Pow(Box<Symbolic>, Box<Symbolic>),
+    Call(&'static Function, Vec<Symbolic>),
+}
+
+// the builders below simplify the trivial terms as they go,
+// such that derivatives don't end up full of `0*x` and `1*...`
+
+fn num(value: f64) -> Symbolic {
+    Symbolic::Number(value)
+}
+
+fn neg(a: Symbolic) -> Symbolic {
+    match a {
+        Symbolic::Number(a) => num(-a),
+        Symbolic::Neg(a) => *a,
+        Symbolic::Mul(a, b) if matches!(*a, Symbolic::Number(_)) => mul(neg(*a), *b),
+        a => Symbolic::Neg(Box::new(a)),
+    }
+}
+
+fn add(a: Symbolic, b: Symbolic) -> Symbolic {
+    match (a, b) {
+        (Symbolic::Number(a), Symbolic::Number(b)) => num(a + b),
+        (Symbolic::Number(0.0), b) => b,
+        (a, Symbolic::Number(0.0)) => a,
+        (a, Symbolic::Neg(b)) => sub(a, *b),
+        (a, b) => Symbolic::Add(Box::new(a), Box::new(b)),
+    }
+}
+
+fn sub(a: Symbolic, b: Symbolic) -> Symbolic {
+    match (a, b) {
+        (Symbolic::Number(a), Symbolic::Number(b)) => num(a - b),
+        (Symbolic::Number(0.0), b) => neg(b),
+        (a, Symbolic::Number(0.0)) => a,
+        (a, b) if a == b => num(0.0),
+        (a, Symbolic::Neg(b)) => add(a, *b),
+        (a, b) => Symbolic::Sub(Box::new(a), Box::new(b)),
+    }
+}
+
+fn mul(a: Symbolic, b: Symbolic) -> Symbolic {
+    match (a, b) {
+        (Symbolic::Number(a), Symbolic::Number(b)) => num(a * b),
+        (Symbolic::Number(0.0), _) | (_, Symbolic::Number(0.0)) => num(0.0),
+        (Symbolic::Number(1.0), b) => b,
+        (a, Symbolic::Number(1.0)) => a,
+        (Symbolic::Number(-1.0), b) => neg(b),
+        (a, Symbolic::Number(-1.0)) => neg(a),
+        (Symbolic::Neg(a), b) => neg(mul(*a, b)),
+        (a, Symbolic::Neg(b)) => neg(mul(a, *b)),
+        (Symbolic::Number(a), Symbolic::Mul(b, c)) if matches!(*b, Symbolic::Number(_)) => {
+            mul(mul(num(a), *b), *c)
+        }
+        // keep the numbers in front, `2*x` reads better than `x*2`
+        (a, b @ Symbolic::Number(_)) => Symbolic::Mul(Box::new(b), Box::new(a)),
+        (a, b) => Symbolic::Mul(Box::new(a), Box::new(b)),
+    }
+}
+
+fn div(a: Symbolic, b: Symbolic) -> Symbolic {
+    match (a, b) {
+        (Symbolic::Number(a), Symbolic::Number(b)) if b != 0.0 => num(a / b),
+        (Symbolic::Number(0.0), _) => num(0.0),
+        (a, Symbolic::Number(1.0)) => a,
+        (a, b) => Symbolic::Div(Box::new(a), Box::new(b)),
+    }
+}
+
+fn pow(a: Symbolic, b: Symbolic) -> Symbolic {
+    match (a, b) {
+        (Symbolic::Number(a), Symbolic::Number(b)) => num(a.powf(b)),
+        (_, Symbolic::Number(0.0)) => num(1.0),
+        (a, Symbolic::Number(1.0)) => a,
+        (a, b) => Symbolic::Pow(Box::new(a), Box::new(b)),
+    }
+}
+
+fn ln(a: Symbolic) -> Symbolic {
+    match a {
+        Symbolic::Number(E) => num(1.0),
+        a => call("ln", vec![a]),
+    }
+}
+
+/// call a function of the registry by its name
+fn call(name: &str, args: Vec<Symbolic>) -> Symbolic {
+    let function = FUNCTIONS
+        .iter()
+        .find(|function| function.name == name)
+        .expect("derivatives only use registered functions");
+    Symbolic::Call(function, args)
+}
+
+impl Symbolic {
+    fn from_node(node: &Node, variables: &'static [Variable]) -> Result<Self, EvalexprError> {
+        let children = node.children();
+        let operand = |i: usize| Self::from_node(&children[i], variables);
+
+        match node.operator() {
+            Operator::RootNode if children.len() == 1 => operand(0),
+            Operator::Const { value } => Ok(num(value.as_number()?)),
+            Operator::VariableIdentifierRead { identifier } => variables
+                .iter()
+                .find(|variable| variable.evaluable == identifier)
+                .map(Symbolic::Variable)
+                .ok_or_else(|| EvalexprError::VariableIdentifierNotFound(identifier.clone())),
+            Operator::Neg => Ok(Symbolic::Neg(Box::new(operand(0)?))),
+            Operator::Add => Ok(Symbolic::Add(Box::new(operand(0)?), Box::new(operand(1)?))),
+            Operator::Sub => Ok(Symbolic::Sub(Box::new(operand(0)?), Box::new(operand(1)?))),
+            Operator::Mul => Ok(Symbolic::Mul(Box::new(operand(0)?), Box::new(operand(1)?))),
+            Operator::Div => Ok(Symbolic::Div(Box::new(operand(0)?), Box::new(operand(1)?))),
+            Operator::Mod => Ok(Symbolic::Mod(Box::new(operand(0)?), Box::new(operand(1)?))),
+            Operator::Exp => Ok(Symbolic::Pow(Box::new(operand(0)?), Box::new(operand(1)?))),
+            Operator::FunctionIdentifier { identifier } => {
+                let function = FUNCTIONS
+                    .iter()
+                    .find(|function| function.evaluable_name() == identifier)
+                    .ok_or_else(|| EvalexprError::FunctionIdentifierNotFound(identifier.clone()))?;
+
+                // the arguments are either a single operand, or a tuple of them
+                let mut argument = &children[0];
+                while *argument.operator() == Operator::RootNode && argument.children().len() == 1 {
+                    argument = &argument.children()[0];
                 }
+                let args = match argument.operator() {
+                    Operator::Tuple => argument
+                        .children()
+                        .iter()
+                        .map(|arg| Self::from_node(arg, variables))
+                        .collect::<Result<Vec<_>, _>>()?,
+                    _ => vec![Self::from_node(argument, variables)?],
+                };
+                Ok(Symbolic::Call(function, args))
+            }
+            operator => Err(EvalexprError::CustomMessage(format!(
+                "'{operator}' can't be handled symbolically"
+            ))),
+        }
+    }
+
+    fn depends_on_variable(&self, wrt: &Variable) -> bool {
+        match self {
+            Symbolic::Number(_) => false,
+            Symbolic::Variable(variable) => *variable == wrt,
+            Symbolic::Neg(a) => a.depends_on_variable(wrt),
+            Symbolic::Add(a, b)
+            | Symbolic::Sub(a, b)
+            | Symbolic::Mul(a, b)
+            | Symbolic::Div(a, b)
+            | Symbolic::Mod(a, b)
+            | Symbolic::Pow(a, b) => a.depends_on_variable(wrt) || b.depends_on_variable(wrt),
+            Symbolic::Call(_, args) => args.iter().any(|arg| arg.depends_on_variable(wrt)),
+        }
+    }
+
+    /// derivative with respect to the `wrt` variable
+    fn derive(&self, wrt: &Variable) -> Result<Self, EvalexprError> {
+        Ok(match self {
+            Symbolic::Number(_) => num(0.0),
+            Symbolic::Variable(variable) => num(if *variable == wrt { 1.0 } else { 0.0 }),
+            Symbolic::Neg(a) => neg(a.derive(wrt)?),
+            Symbolic::Add(a, b) => add(a.derive(wrt)?, b.derive(wrt)?),
+            Symbolic::Sub(a, b) => sub(a.derive(wrt)?, b.derive(wrt)?),
+            Symbolic::Mul(a, b) => add(
+                mul(a.derive(wrt)?, *b.clone()),
+                mul(*a.clone(), b.derive(wrt)?),
+            ),
+            Symbolic::Div(a, b) if !b.depends_on_variable(wrt) => div(a.derive(wrt)?, *b.clone()),
+            Symbolic::Div(a, b) => div(
+                sub(
+                    mul(a.derive(wrt)?, *b.clone()),
+                    mul(*a.clone(), b.derive(wrt)?),
+                ),
+                pow(*b.clone(), num(2.0)),
+            ),
+            // a % b = a - b * trunc(a/b), with trunc(a/b) = (a - a % b) / b
+            Symbolic::Mod(a, b) => sub(
+                a.derive(wrt)?,
+                mul(
+                    b.derive(wrt)?,
+                    div(sub(*a.clone(), self.clone()), *b.clone()),
+                ),
+            ),
+            Symbolic::Pow(a, b) => Self::derive_pow(a, b, wrt)?,
+            Symbolic::Call(function, args) => Self::derive_call(function, args, wrt)?,
+        })
+    }
+
+    fn derive_pow(a: &Symbolic, b: &Symbolic, wrt: &Variable) -> Result<Self, EvalexprError> {
+        let (a, b) = (a.clone(), b.clone());
+        Ok(
+            match (a.depends_on_variable(wrt), b.depends_on_variable(wrt)) {
+                (_, false) => mul(
+                    mul(b.clone(), pow(a.clone(), sub(b, num(1.0)))),
+                    a.derive(wrt)?,
+                ),
+                (false, true) => mul(mul(pow(a.clone(), b.clone()), ln(a)), b.derive(wrt)?),
+                (true, true) => mul(
+                    pow(a.clone(), b.clone()),
+                    add(
+                        mul(b.derive(wrt)?, ln(a.clone())),
+                        div(mul(b, a.derive(wrt)?), a),
+                    ),
+                ),
+            },
+        )
+    }
+
+    fn derive_call(
+        function: &Function,
+        args: &[Symbolic],
+        wrt: &Variable,
+    ) -> Result<Self, EvalexprError> {
+        let not_derivable = || {
+            EvalexprError::CustomMessage(format!(
+                "can't derive {} with {} arguments",
+                function.name,
+                args.len()
+            ))
+        };
+        let sqrt = |a: Symbolic| call("sqrt", vec![a]);
+        let square = |a: Symbolic| pow(a, num(2.0));
+
+        // derivative of the function itself, for the chain rule
+        let outer = match (function.name, args) {
+            ("sin", [u]) => call("cos", vec![u.clone()]),
+            ("cos", [u]) => neg(call("sin", vec![u.clone()])),
+            ("tan", [u]) => div(num(1.0), square(call("cos", vec![u.clone()]))),
+            ("asin", [u]) => div(num(1.0), sqrt(sub(num(1.0), square(u.clone())))),
+            ("acos", [u]) => neg(div(num(1.0), sqrt(sub(num(1.0), square(u.clone()))))),
+            ("atan", [u]) => div(num(1.0), add(num(1.0), square(u.clone()))),
+            ("sinh", [u]) => call("cosh", vec![u.clone()]),
+            ("cosh", [u]) => call("sinh", vec![u.clone()]),
+            ("tanh", [u]) => div(num(1.0), square(call("cosh", vec![u.clone()]))),
+            ("asinh", [u]) => div(num(1.0), sqrt(add(square(u.clone()), num(1.0)))),
+            ("acosh", [u]) => div(num(1.0), sqrt(sub(square(u.clone()), num(1.0)))),
+            ("atanh", [u]) => div(num(1.0), sub(num(1.0), square(u.clone()))),
+            ("exp", [u]) => call("exp", vec![u.clone()]),
+            ("exp2", [u]) => mul(call("exp2", vec![u.clone()]), num(LN_2)),
+            ("ln", [u]) => div(num(1.0), u.clone()),
+            ("log2", [u]) => div(num(1.0), mul(u.clone(), num(LN_2))),
+            ("log10", [u]) => div(num(1.0), mul(u.clone(), num(LN_10))),
+            ("sqrt", [u]) => div(num(1.0), mul(num(2.0), sqrt(u.clone()))),
+            ("cbrt", [u]) => div(
+                num(1.0),
+                mul(num(3.0), square(call("cbrt", vec![u.clone()]))),
+            ),
+            ("abs", [u]) => call("sign", vec![u.clone()]),
+            ("sigmoid", [u]) => {
+                let sigmoid = call("sigmoid", vec![u.clone()]);
+                mul(sigmoid.clone(), sub(num(1.0), sigmoid))
+            }
+            // piecewise constant functions, their derivative is 0 almost everywhere
+            ("floor" | "ceil" | "round" | "sign" | "step", [_]) | ("bump", [_, _, _]) => num(0.0),
+
+            // functions of several arguments are rewritten with simpler ones
+            ("log", [u, base]) => return div(ln(u.clone()), ln(base.clone())).derive(wrt),
+            ("pow", [a, b]) => return Self::derive_pow(a, b, wrt),
+            ("hypot", [a, b]) => {
+                return sqrt(add(square(a.clone()), square(b.clone()))).derive(wrt);
+            }
+            ("atan2", [y, x]) => {
+                return Ok(div(
+                    sub(
+                        mul(x.clone(), y.derive(wrt)?),
+                        mul(y.clone(), x.derive(wrt)?),
+                    ),
+                    add(square(x.clone()), square(y.clone())),
+                ));
+            }
+            ("mod", [a, m]) => {
+                let quotient = call("floor", vec![div(a.clone(), m.clone())]);
+                return Ok(sub(a.derive(wrt)?, mul(m.derive(wrt)?, quotient)));
+            }
+            // min(a, b) is a where b - a >= 0, b elsewhere, and conversely for max
+            ("min" | "max", [a, b]) => {
+                let a_wins = match function.name {
+                    "min" => call("step", vec![sub(b.clone(), a.clone())]),
+                    _ => call("step", vec![sub(a.clone(), b.clone())]),
+                };
+                return Ok(add(
+                    mul(a_wins.clone(), a.derive(wrt)?),
+                    mul(sub(num(1.0), a_wins), b.derive(wrt)?),
+                ));
+            }
+            ("clamp", [u, low, high]) => {
+                return Ok(add(
+                    add(
+                        mul(
+                            call("bump", vec![u.clone(), low.clone(), high.clone()]),
+                            u.derive(wrt)?,
+                        ),
+                        mul(
+                            call("step", vec![sub(low.clone(), u.clone())]),
+                            low.derive(wrt)?,
+                        ),
+                    ),
+                    mul(
+                        call("step", vec![sub(u.clone(), high.clone())]),
+                        high.derive(wrt)?,
+                    ),
+                ));
+            }
+            _ => return Err(not_derivable()),
+        };
+        Ok(mul(outer, args[0].derive(wrt)?))
+    }
+
+    /// rebuild the tree with the builders, which simplifies it
+    fn simplify(&self) -> Self {
+        let binary = |a: &Symbolic, b: &Symbolic, build: fn(Symbolic, Symbolic) -> Symbolic| {
+            build(a.simplify(), b.simplify())
+        };
+        match self {
+            Symbolic::Number(_) | Symbolic::Variable(_) => self.clone(),
+            Symbolic::Neg(a) => neg(a.simplify()),
+            Symbolic::Add(a, b) => binary(a, b, add),
+            Symbolic::Sub(a, b) => binary(a, b, sub),
+            Symbolic::Mul(a, b) => binary(a, b, mul),
+            Symbolic::Div(a, b) => binary(a, b, div),
+            Symbolic::Mod(a, b) => Symbolic::Mod(Box::new(a.simplify()), Box::new(b.simplify())),
+            Symbolic::Pow(a, b) => binary(a, b, pow),
+            Symbolic::Call(function, args) => {
+                Symbolic::Call(function, args.iter().map(Symbolic::simplify).collect())
+            }
+        }
+    }
+
+    /// binding strength, used to only write the parentheses that are needed
+    fn precedence(&self) -> u8 {
+        match self {
+            Symbolic::Add(..) | Symbolic::Sub(..) => 1,
+            Symbolic::Mul(..) | Symbolic::Div(..) | Symbolic::Mod(..) => 2,
+            Symbolic::Neg(_) => 3,
+            Symbolic::Number(value) if *value < 0.0 => 3,
+            Symbolic::Pow(..) => 4,
+            Symbolic::Number(_) | Symbolic::Variable(_) | Symbolic::Call(..) => 5,
+        }
+    }
+
+    /// write the expression either for the player (`readable`) or for evalexpr
+    fn write(&self, readable: bool) -> String {
+        let operand = |a: &Symbolic, precedence: u8| match a.precedence() < precedence {
+            true => format!("({})", a.write(readable)),
+            false => a.write(readable),
+        };
+        match self {
+            Symbolic::Number(value) if readable => CONSTANTS
+                .iter()
+                .find(|(_, constant)| constant == value)
+                .map_or(format!("{value}"), |(name, _)| name.to_string()),
+            // numbers are always written as floats, otherwise evalexpr does integer divisions
+            Symbolic::Number(value) => format!("{value:?}"),
+            Symbolic::Variable(variable) => match readable {
+                true => variable.name.to_string(),
+                false => variable.evaluable.to_string(),
+            },
+            Symbolic::Neg(a) => format!("-{}", operand(a, 4)),
+            Symbolic::Add(a, b) => format!("{} + {}", operand(a, 1), operand(b, 1)),
+            Symbolic::Sub(a, b) => format!("{} - {}", operand(a, 1), operand(b, 2)),
+            Symbolic::Mul(a, b) => format!("{}*{}", operand(a, 2), operand(b, 2)),
+            Symbolic::Div(a, b) => format!("{}/{}", operand(a, 2), operand(b, 3)),
+            Symbolic::Mod(a, b) => format!("{} % {}", operand(a, 2), operand(b, 3)),
+            Symbolic::Pow(a, b) => format!("{}^{}", operand(a, 5), operand(b, 5)),
+            Symbolic::Call(function, args) => format!(
+                "{}({})",
+                match readable {
+                    true => function.name,
+                    false => function.evaluable_name(),
+                },
+                args.iter()
+                    .map(|arg| arg.write(readable))
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn eval(raw_expr: &str, x: f64) -> f64 {
+        MathExpression::new(raw_expr)
+            .unwrap_or_else(|why| panic!("{raw_expr}: {}", why.error))
+            .compute(x)
+            .unwrap()
+    }
+
+    fn tokens(raw_expr: &str) -> Vec<Token> {
+        MathExpression::tokenize(raw_expr, FUNCTION_VARIABLES)
+            .unwrap()
+            .into_iter()
+            .map(|(token, _)| token)
+            .collect()
+    }
+
+    fn error_span(raw_expr: &str) -> Option<Range<usize>> {
+        MathExpression::new(raw_expr)
+            .err()
+            .unwrap_or_else(|| panic!("{raw_expr} should be rejected"))
+            .span
+    }
+
+    #[test]
+    fn functions_containing_x() {
+        assert_eq!(eval("exp(x)", 1.0), E);
+        assert_eq!(eval("max(x, 1)", 3.0), 3.0);
+        assert_eq!(eval("exp2(x) + x", 3.0), 11.0);
+    }
+
+    #[test]
+    fn implicit_multiplication() {
+        assert_eq!(
+            tokens("2x"),
+            [
+                Token::Number(2.0),
+                Token::Operator('*'),
+                Token::Variable(&X)
+            ]
+        );
+        assert_eq!(
+            tokens("xpi"),
+            [
+                Token::Variable(&X),
+                Token::Operator('*'),
+                Token::Constant(PI)
+            ]
+        );
+        assert_eq!(eval("3sin(x)", PI / 2.0), 3.0);
+        assert_eq!(eval("(x+1)(x-1)", 3.0), 8.0);
+        assert_eq!(eval("x2", 3.0), 6.0);
+    }
+
+    #[test]
+    fn longest_identifier_first() {
+        assert_eq!(tokens("exp(x)")[0], Token::Function("math::exp"));
+        assert_eq!(
+            tokens("e x"),
+            [
+                Token::Constant(E),
+                Token::Operator('*'),
+                Token::Variable(&X)
+            ]
+        );
+        assert_eq!(eval("ex", 2.0), 2.0 * E);
+    }
+
+    #[test]
+    fn unicode_glyphs() {
+        assert_eq!(eval("πx", 2.0), 2.0 * PI);
+        assert_eq!(eval("τ + x", 0.0), TAU);
+        assert_eq!(eval("√x", 4.0), 2.0);
+        assert_eq!(eval("2√(x+1)", 3.0), 4.0);
+        assert_eq!(eval("x²", 3.0), 9.0);
+        assert_eq!(eval("x³ + x²", 2.0), 12.0);
+        assert_eq!(eval("3×x", 2.0), 6.0);
+        assert_eq!(eval("x÷4", 2.0), 0.5);
+        assert_eq!(eval("−x − 1", 2.0), -3.0);
+        let polar = MathExpression::with_variables("2θ", POLAR_VARIABLES).unwrap();
+        assert_eq!(polar.compute(1.5).unwrap(), 3.0);
+    }
+
+    #[test]
+    fn unicode_glyphs_error_spans() {
+        // the spans are on the glyphs, not on their longer plain text form
+        assert_eq!(error_span("x²−"), Some(2..3));
+        assert_eq!(error_span("√"), Some(0..1));
+        assert_eq!(error_span("π + √"), Some(4..5));
+        assert_eq!(error_span("x ÷ × 2"), Some(2..3));
+        assert_eq!(error_span("√(x −"), Some(4..5));
+        assert_eq!(error_span("τ²x @"), Some(4..5));
+    }
+
+    #[test]
+    fn custom_functions() {
+        assert_eq!(step(&[-0.1]), 0.0);
+        assert_eq!(step(&[0.0]), 1.0);
+        assert_eq!(step(&[2.0]), 1.0);
+        assert_eq!(sigmoid(&[0.0]), 0.5);
+        assert!(sigmoid(&[-1000.0]) == 0.0 && sigmoid(&[1000.0]) == 1.0);
+        assert!((sigmoid(&[1.0]) + sigmoid(&[-1.0]) - 1.0).abs() < 1e-12);
+        // 1 on the whole closed interval only
+        assert_eq!(bump(&[1.0, 1.0, 2.0]), 1.0);
+        assert_eq!(bump(&[2.0, 1.0, 2.0]), 1.0);
+        assert_eq!(bump(&[0.9, 1.0, 2.0]), 0.0);
+        assert_eq!(bump(&[2.1, 1.0, 2.0]), 0.0);
+        assert_eq!(bump(&[1.5, 2.0, 1.0]), 0.0); // an empty interval
+        assert_eq!(clamp(&[5.0, 1.0, 3.0]), 3.0);
+        assert_eq!(clamp(&[-5.0, 1.0, 3.0]), 1.0);
+        assert_eq!(clamp(&[2.0, 1.0, 3.0]), 2.0);
+        assert_eq!(clamp(&[5.0, 3.0, 1.0]), 3.0);
+        assert_eq!(clamp(&[-5.0, 3.0, 1.0]), 1.0);
+        assert_eq!(modulo(&[7.0, 3.0]), 1.0);
+        assert_eq!(modulo(&[-1.0, 3.0]), 2.0);
+        assert!(modulo(&[1.0, 0.0]).is_nan());
+        assert_eq!(sign(&[-2.0]), -1.0);
+        assert_eq!(sign(&[0.0]), 0.0);
+        assert_eq!(sign(&[-0.0]), 0.0);
+        assert_eq!(sign(&[3.0]), 1.0);
+        // through the evaluation context
+        assert_eq!(eval("bump(x, 1, 2) + step(x)", 1.5), 2.0);
+        assert_eq!(eval("clamp(x, 1, 3)", 5.0), 3.0);
+    }
+
+    #[test]
+    fn wrong_argument_counts() {
+        assert_eq!(error_span("max(x)"), Some(0..3));
+        assert_eq!(error_span("2 + sin(x, 1)"), Some(4..7));
+        assert_eq!(error_span("clamp(x, 1)"), Some(0..5));
+        assert_eq!(error_span("sin(max x)"), Some(4..7));
+        assert_eq!(error_span("mod(x, (x, 1))"), Some(9..10));
+        let message = match MathExpression::new("max(x)").map(|_| ()) {
+            Err(ExpressionError {
+                error: EvalexprError::CustomMessage(message),
+                ..
+            }) => message,
+            _ => panic!("max(x) should be rejected with a message"),
+        };
+        assert!(message.starts_with("max takes at least 2 arguments, not 1"));
+
+        assert_eq!(eval("max(x, 1, 5)", 3.0), 5.0);
+        assert_eq!(eval("sin x", 0.0), 0.0);
+        assert_eq!(eval("mod(max(x, 2), 3)", 4.0), 1.0);
+    }
+
+    #[test]
+    fn ambiguous_identifiers() {
+        assert_eq!(eval("log2x", 8.0), 3.0);
+        assert_eq!(eval("log10(x)x2", 100.0), 400.0);
+        // each `log2` can be cut two ways, a typo at the end used to try them all
+        let typo = format!("{}q", "log2".repeat(200));
+        assert_eq!(error_span(&typo), Some(0..typo.len()));
+    }
+
+    #[test]
+    fn absolute_value_bars() {
+        assert_eq!(eval("|x|", -3.0), 3.0);
+        assert_eq!(eval("|x - |x||", -2.0), 4.0);
+        assert_eq!(eval("2|x|", -1.0), 2.0);
+        assert_eq!(error_span("|x"), Some(0..1));
+    }
+
+    #[test]
+    fn scientific_notation() {
+        assert_eq!(eval("1e3*x", 2.0), 2000.0);
+        assert_eq!(eval("2.5E-1x", 2.0), 0.5);
+        assert_eq!(eval("1e+2 + x", 1.0), 101.0);
+        assert_eq!(eval("x2e3", 1.0), 2000.0);
+        // without digits after it, e is still the constant
+        assert_eq!(eval("2ex", 1.0), 2.0 * E);
+        assert_eq!(eval("2e - x", 1.0), 2.0 * E - 1.0);
+    }
+
+    #[test]
+    fn python_power() {
+        assert_eq!(eval("x**2", 3.0), 9.0);
+    }
+
+    #[test]
+    fn constant_lines() {
+        for raw_expr in ["exp(1)", "x-x", "0*x", "sin(x)^0", "x^2 - x*x"] {
+            assert!(MathExpression::new(raw_expr).is_err(), "{raw_expr}");
+        }
+        // only varies on a narrow piece of the board
+        assert_eq!(eval("bump(x, 1, 1.2)", 1.1), 1.0);
+        assert_eq!(eval("sin(pi*x)", 0.5), 1.0);
+    }
+
+    #[test]
+    fn signs() {
+        assert_eq!(eval("+x", 2.0), 2.0);
+        assert_eq!(eval("x*+2", 2.0), 4.0);
+        assert_eq!(eval("-x + 1", 2.0), -1.0);
+        assert_eq!(error_span("x+"), Some(1..2));
+    }
+
+    #[test]
+    fn commas_outside_functions() {
+        assert_eq!(error_span("x,1"), Some(1..2));
+        assert_eq!(error_span("(x, 1)"), Some(2..3));
+        assert_eq!(error_span("max((x, 1), 2)"), Some(6..7));
+        assert_eq!(eval("max(x, (1))", 3.0), 3.0);
+    }
+
+    fn derivative(raw_expr: &str) -> MathExpression {
+        MathExpression::new(raw_expr)
+            .unwrap_or_else(|why| panic!("{raw_expr}: {}", why.error))
+            .derivative()
+            .unwrap_or_else(|why| panic!("{raw_expr}: {why}"))
+    }
+
+    #[test]
+    fn derivative_rules() {
+        for raw_expr in [
+            "x^3 - 2x",
+            "sin(x)cos(x)",
+            "1/(x^2 + 1)",
+            "exp(2x)",
+            "ln(x)",
+            "sqrt(x)",
+            "2^x",
+            "x^x",
+            "atan(x)",
+            "log(x, 3)",
+            "hypot(x, 2)",
+            "atan2(x, 2)",
+            "sigmoid(x)",
+        ] {
+            let (function, derivative) =
+                (MathExpression::new(raw_expr).unwrap(), derivative(raw_expr));
+            for x in [0.7, 1.3, 2.9] {
+                let h = 1e-6;
+                let expected = (function.compute(x + h).unwrap()
+                    - function.compute(x - h).unwrap())
+                    / (2.0 * h);
+                let actual = derivative.compute(x).unwrap();
+                assert!(
+                    (expected - actual).abs() < 1e-4 * expected.abs().max(1.0),
+                    "d/dx {raw_expr} at {x}: {actual} instead of {expected}"
+                );
             }
         }
-        evaluable_expr
+    }
+
+    #[test]
+    fn simplified_derivatives() {
+        assert_eq!(derivative("x^2").to_string(), "2*x");
+        assert_eq!(derivative("3x + 1").to_string(), "3");
+        assert_eq!(derivative("sin(x)").to_string(), "cos(x)");
+        assert_eq!(derivative("exp(x)").to_string(), "exp(x)");
+        assert_eq!(derivative("-x").to_string(), "-1");
+        assert_eq!(derivative("floor(x)").to_string(), "0");
+    }
+
+    #[test]
+    fn simplify() {
+        let simplified = |raw_expr: &str| MathExpression::new(raw_expr).unwrap().to_string();
+        assert_eq!(simplified("0*x + 1*x"), "x");
+        assert_eq!(simplified("x^1 - 0"), "x");
+        assert_eq!(simplified("x*2"), "2*x");
+        assert_eq!(simplified("-(-x)"), "x");
+        assert_eq!(simplified("3.141592653589793x"), "pi*x");
+    }
+
+    #[test]
+    fn unknown_identifiers() {
+        assert_eq!(error_span("foo(x)"), Some(0..3));
+        assert_eq!(error_span("x + y"), Some(4..5));
     }
 }
@@ -0,0 +1,286 @@
+use std::{fmt, fs, io, path::Path};
+
+use egui::plot::PlotPoint;
+use serde::{Deserialize, Serialize};
+
+use crate::plotter::{distance, BoardConfig, DiscontinuityRule, ENTITY_AMPLITUDE};
+
+pub const OBSTACLE_SIDES: usize = 20; // the generated obstacles are nearly round
+const MAX_OBSTACLE_SIDES: usize = 64; // collisions are checked against each side
+
+/// a hand-authored board, written in TOML:
+///
+/// ```toml
+/// board = { width = 50, height = 50 }
+/// player = [-20, 0]
+/// enemies = [[15, 10], [20, -12]]
+/// obstacles = [{ position = [0, 0], radius = 4, sides = 6 }]
+/// rules = { anchored_shots = true, discontinuity_rule = "stop" }
+/// ```
+#[derive(Clone, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct Level {
+    #[serde(default)]
+    pub board: BoardConfig,
+    pub player: [f64; 2],
+    pub enemies: Vec<[f64; 2]>,
+    #[serde(default)]
+    pub obstacles: Vec<LevelObstacle>,
+    #[serde(default)]
+    pub rules: LevelRules,
+}
+
+#[derive(Clone, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct LevelObstacle {
+    pub position: [f64; 2],
+    pub radius: f64,
+    #[serde(default = "LevelObstacle::default_sides")]
+    pub sides: usize, // the obstacle is a regular polygon inscribed in its radius
+}
+
+impl LevelObstacle {
+    fn default_sides() -> usize {
+        OBSTACLE_SIDES
+    }
+}
+
+/// settings the level enforces, the player's settings are kept for the missing ones
+#[derive(Clone, Default, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct LevelRules {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub anchored_shots: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub discontinuity_rule: Option<DiscontinuityRule>,
+}
+
+pub enum LevelError {
+    Read(io::Error),
+    Parse(toml::de::Error),
+    Write(io::Error),
+    Serialize(toml::ser::Error),
+    Invalid(Vec<String>), // every problem found in the level
+}
+
+impl fmt::Display for LevelError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LevelError::Read(why) => write!(f, "can't read the level file: {why}"),
+            LevelError::Parse(why) => write!(f, "the level file is malformed: {why}"),
+            LevelError::Write(why) => write!(f, "can't write the level file: {why}"),
+            LevelError::Serialize(why) => write!(f, "can't write the level: {why}"),
+            LevelError::Invalid(problems) => {
+                write!(f, "the level is invalid: {}", problems.join(", "))
+            }
+        }
+    }
+}
+
+impl Level {
+    pub fn load(path: &Path) -> Result<Self, LevelError> {
+        let content = fs::read_to_string(path).map_err(LevelError::Read)?;
+        let level: Level = toml::from_str(&content).map_err(LevelError::Parse)?;
+        level.validate()?;
+
+        Ok(level)
+    }
+
+    /// write the level at `path`, if it is valid
+    pub fn save(&self, path: &Path) -> Result<(), LevelError> {
+        self.validate()?;
+        let content = toml::to_string(self).map_err(LevelError::Serialize)?;
+        fs::write(path, content).map_err(LevelError::Write)
+    }
+
+    /// the entities as circles: (name, position, radius)
+    fn circles(&self) -> Vec<(String, PlotPoint, f64)> {
+        let point = |[x, y]: [f64; 2]| PlotPoint::new(x, y);
+        std::iter::once((
+            "the player".to_string(),
+            point(self.player),
+            ENTITY_AMPLITUDE,
+        ))
+        .chain(
+            self.enemies
+                .iter()
+                .enumerate()
+                .map(|(id, &pos)| (format!("enemy {}", id + 1), point(pos), ENTITY_AMPLITUDE)),
+        )
+        .chain(self.obstacles.iter().enumerate().map(|(id, obstacle)| {
+            (
+                format!("obstacle {}", id + 1),
+                point(obstacle.position),
+                obstacle.radius,
+            )
+        }))
+        .collect()
+    }
+
+    pub fn validate(&self) -> Result<(), LevelError> {
+        let mut problems = vec![];
+        let board = &self.board;
+        // the same limits as in the settings
+        let sizes = BoardConfig::MIN_SIZE..=BoardConfig::MAX_SIZE;
+        if !sizes.contains(&board.width) || !sizes.contains(&board.height) {
+            problems.push(format!(
+                "the board width and height must be between {} and {}",
+                BoardConfig::MIN_SIZE,
+                BoardConfig::MAX_SIZE
+            ));
+        }
+        if !(0.0..=BoardConfig::MAX_MARGIN).contains(&board.margin) {
+            problems.push(format!(
+                "the board margin must be between 0 and {}",
+                BoardConfig::MAX_MARGIN
+            ));
+        }
+        if self.enemies.is_empty() {
+            problems.push("a level needs at least one enemy".to_string());
+        }
+        for (id, obstacle) in self.obstacles.iter().enumerate() {
+            if obstacle.radius <= 0.0 {
+                problems.push(format!("obstacle {} needs a positive radius", id + 1));
+            }
+            if !(3..=MAX_OBSTACLE_SIDES).contains(&obstacle.sides) {
+                problems.push(format!(
+                    "obstacle {} needs between 3 and {MAX_OBSTACLE_SIDES} sides",
+                    id + 1
+                ));
+            }
+        }
+
+        let ((x_min, x_max), (y_min, y_max)) = (board.x_bounds(), board.y_bounds());
+        let circles = self.circles();
+        for (name, pos, radius) in &circles {
+            let is_inside = (x_min + radius..=x_max - radius).contains(&pos.x)
+                && (y_min + radius..=y_max - radius).contains(&pos.y);
+            if !is_inside {
+                problems.push(format!("{name} is out of the board"));
+            }
+        }
+        for (id, (name, pos, radius)) in circles.iter().enumerate() {
+            for (other_name, other_pos, other_radius) in &circles[id + 1..] {
+                if distance(pos, other_pos) < radius + other_radius {
+                    problems.push(format!("{name} overlaps {other_name}"));
+                }
+            }
+        }
+
+        match problems.is_empty() {
+            true => Ok(()),
+            false => Err(LevelError::Invalid(problems)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn level() -> Level {
+        toml::from_str(
+            r#"
+            player = [-20, 0]
+            enemies = [[15, 10]]
+            obstacles = [{ position = [0, 0], radius = 4, sides = 6 }]
+            "#,
+        )
+        .unwrap()
+    }
+
+    fn problems(level: &Level) -> Vec<String> {
+        match level.validate() {
+            Err(LevelError::Invalid(problems)) => problems,
+            Err(why) => panic!("{why}"),
+            Ok(()) => vec![],
+        }
+    }
+
+    #[test]
+    fn example_level_is_valid() {
+        let level: Level = toml::from_str(include_str!("../levels/example.toml")).unwrap();
+        assert!(problems(&level).is_empty());
+    }
+
+    #[test]
+    fn defaults() {
+        let level = level();
+        assert!(level.board == BoardConfig::default());
+        assert!(level.rules.anchored_shots.is_none());
+        assert!(problems(&level).is_empty());
+    }
+
+    #[test]
+    fn out_of_board() {
+        let mut level = level();
+        level.enemies.push([24.5, 0.0]); // sticks out of the edge
+        level.obstacles[0].position = [0.0, -30.0];
+        assert_eq!(
+            problems(&level),
+            [
+                "enemy 2 is out of the board",
+                "obstacle 1 is out of the board"
+            ]
+        );
+    }
+
+    #[test]
+    fn overlaps() {
+        let mut level = level();
+        level.enemies.push([3.0, 3.0]);
+        level.player = [15.0, 11.0];
+        assert_eq!(
+            problems(&level),
+            ["the player overlaps enemy 1", "enemy 2 overlaps obstacle 1"]
+        );
+    }
+
+    #[test]
+    fn malformed_entities() {
+        let mut level = level();
+        level.enemies.clear();
+        level.obstacles[0].sides = 2;
+        level.obstacles.push(LevelObstacle {
+            position: [10.0, -10.0],
+            radius: 0.0,
+            sides: 100_000_000,
+        });
+        level.board.width = 500.0;
+        assert_eq!(problems(&level).len(), 5);
+    }
+
+    #[test]
+    fn unknown_fields() {
+        let parsed = toml::from_str::<Level>("player = [0, 0]\nenemis = [[1, 1]]");
+        assert!(parsed.is_err());
+    }
+
+    #[test]
+    fn save_and_load() {
+        let mut level = level();
+        level.rules.discontinuity_rule = Some(DiscontinuityRule::Jump);
+        let path = std::env::temp_dir().join(format!("graphwar_level_{}.toml", std::process::id()));
+        level.save(&path).unwrap_or_else(|why| panic!("{why}"));
+        let loaded = Level::load(&path).unwrap_or_else(|why| panic!("{why}"));
+        fs::remove_file(&path).unwrap();
+
+        assert!(loaded.board == level.board);
+        assert_eq!(loaded.player, level.player);
+        assert_eq!(loaded.enemies, level.enemies);
+        assert_eq!(loaded.obstacles[0].position, level.obstacles[0].position);
+        assert_eq!(loaded.obstacles[0].radius, level.obstacles[0].radius);
+        assert_eq!(loaded.obstacles[0].sides, level.obstacles[0].sides);
+        assert!(loaded.rules.anchored_shots.is_none());
+        assert!(loaded.rules.discontinuity_rule == Some(DiscontinuityRule::Jump));
+    }
+
+    #[test]
+    fn invalid_levels_are_not_saved() {
+        let mut level = level();
+        level.enemies.clear();
+        let path = std::env::temp_dir().join("graphwar_invalid_level.toml");
+        assert!(matches!(level.save(&path), Err(LevelError::Invalid(_))));
+        assert!(!path.exists());
+    }
+}
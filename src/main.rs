@@ -1,7 +1,9 @@
 #![cfg_attr(not(debug_assertions), windows_subsystem = "windows")] // hide console window on Windows in release
 
 pub mod app;
+mod editor;
 mod eval;
+mod level;
 mod plotter;
 mod ui;
 
@@ -20,6 +22,13 @@ fn main() -> Result<(), eframe::Error> {
     eframe::run_native(
         "Graphwar",
         options,
-        Box::new(|_cc| Box::<GraphWar>::default()),
+        Box::new(|_cc| {
+            let mut app = GraphWar::default();
+            // a level file may be given as first argument
+            if let Some(level_path) = std::env::args().nth(1) {
+                app.load_level_file(&level_path);
+            }
+            Box::new(app)
+        }),
     )
 }
@@ -1,28 +1,449 @@
-use std::f64::consts::PI;
+use std::{f64::consts::PI, ops::Range};
 
 use egui::{
-    plot::{Line, Plot, PlotPoint, PlotPoints, PlotUi, Polygon},
+    plot::{Line, LineStyle, MarkerShape, Plot, PlotPoint, PlotPoints, PlotUi, Points, Polygon},
     Color32,
 };
 
+use serde::{Deserialize, Serialize};
+
 use crate::eval::MathExpression;
 
+/// radius of the player and of the enemies
+pub const ENTITY_AMPLITUDE: f64 = 1.0;
+
+/// dimensions of the playfield, which is centered on the origin
+#[derive(Clone, Copy, PartialEq, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct BoardConfig {
+    pub width: f64,
+    pub height: f64,
+    pub margin: f64, // entities spawn at least this far from the edges
+}
+
+impl BoardConfig {
+    // the limits of the settings and of the levels
+    pub const MIN_SIZE: f64 = 20.0;
+    pub const MAX_SIZE: f64 = 100.0;
+    pub const MAX_MARGIN: f64 = 5.0;
+
+    pub const PRESETS: [(&'static str, BoardConfig); 3] = [
+        ("classic", BoardConfig::new(50.0, 50.0, 0.0)),
+        ("wide", BoardConfig::new(80.0, 40.0, 1.0)),
+        ("quick play", BoardConfig::new(30.0, 30.0, 1.0)),
+    ];
+
+    pub const fn new(width: f64, height: f64, margin: f64) -> Self {
+        Self {
+            width,
+            height,
+            margin,
+        }
+    }
+
+    pub fn x_bounds(&self) -> (f64, f64) {
+        (-self.width / 2.0, self.width / 2.0)
+    }
+
+    pub fn y_bounds(&self) -> (f64, f64) {
+        (-self.height / 2.0, self.height / 2.0)
+    }
+
+    pub fn aspect_ratio(&self) -> f64 {
+        self.width / self.height
+    }
+
+    pub fn contains(&self, point: &PlotPoint) -> bool {
+        let ((x_min, x_max), (y_min, y_max)) = (self.x_bounds(), self.y_bounds());
+        (x_min..=x_max).contains(&point.x) && (y_min..=y_max).contains(&point.y)
+    }
+}
+
+impl Default for BoardConfig {
+    fn default() -> Self {
+        BoardConfig::PRESETS[0].1
+    }
+}
+
+/// what a discontinuity of the line means for the shot
+#[derive(Clone, Copy, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DiscontinuityRule {
+    Stop, // the shot ends there
+    Jump, // the shot goes on with the next continuous part
+}
+
+/// where a shot travels, from the player to one of the board edges
+#[derive(Clone, Copy, PartialEq)]
+pub enum ShotDirection {
+    Left,
+    Right,
+}
+
+impl ShotDirection {
+    /// players shoot toward the other side of the board
+    pub fn facing_from(from_point: &PlotPoint) -> Self {
+        match from_point.x <= 0.0 {
+            true => ShotDirection::Right,
+            false => ShotDirection::Left,
+        }
+    }
+
+    fn sign(self) -> f64 {
+        match self {
+            ShotDirection::Left => -1.0,
+            ShotDirection::Right => 1.0,
+        }
+    }
+
+    /// distance between `from_point` and the board edge the shot heads to
+    fn distance_to_edge(self, from_point: &PlotPoint, board: &BoardConfig) -> f64 {
+        let (x_min, x_max) = board.x_bounds();
+        match self {
+            ShotDirection::Left => (from_point.x - x_min).max(0.0),
+            ShotDirection::Right => (x_max - from_point.x).max(0.0),
+        }
+    }
+}
+
+/// the points of a shot in the order of the shot, split into continuous segments
+#[derive(Default)]
+pub struct Trajectory {
+    pub points: Vec<PlotPoint>,
+    segment_starts: Vec<usize>, // indices of the points right after a discontinuity
+}
+
+impl Trajectory {
+    fn push(&mut self, point: PlotPoint) {
+        self.points.push(point);
+    }
+
+    /// the next pushed point starts a new segment
+    fn break_segment(&mut self) {
+        let next_point = self.points.len();
+        if next_point != 0 && self.segment_starts.last() != Some(&next_point) {
+            self.segment_starts.push(next_point);
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.points.is_empty()
+    }
+
+    /// the ranges of points of each continuous segment
+    fn segment_ranges(&self) -> impl Iterator<Item = Range<usize>> + '_ {
+        let starts = std::iter::once(0).chain(self.segment_starts.iter().copied());
+        let ends = self
+            .segment_starts
+            .iter()
+            .copied()
+            .chain([self.points.len()]);
+        starts
+            .zip(ends)
+            .map(|(start, end)| start..end)
+            .filter(|range| !range.is_empty())
+    }
+
+    pub fn segments(&self) -> impl Iterator<Item = &[PlotPoint]> {
+        self.segment_ranges().map(|range| &self.points[range])
+    }
+
+    /// each pair of linked points, with the index of the first one.
+    /// a lonely point is linked to itself, so that it is not forgotten
+    pub fn links(&self) -> impl Iterator<Item = (usize, PlotPoint, PlotPoint)> + '_ {
+        self.segment_ranges().flat_map(move |range| {
+            let last = range.end - 1;
+            (range.start..last.max(range.start + 1))
+                .map(move |i| (i, self.points[i], self.points[(i + 1).min(last)]))
+        })
+    }
+
+    /// keep the points until `last_point_id` included
+    pub fn truncate(&mut self, last_point_id: usize) {
+        self.points.truncate(last_point_id + 1);
+        self.segment_starts.retain(|&start| start <= last_point_id);
+    }
+
+    /// end the trajectory at `point`, which lies after the point `point_id`
+    pub fn cut_at(&mut self, point_id: usize, point: PlotPoint) {
+        self.truncate(point_id);
+        self.points.push(point);
+    }
+
+    /// drop everything after the first discontinuity
+    pub fn keep_first_segment(&mut self) {
+        if let Some(&start) = self.segment_starts.first() {
+            self.truncate(start - 1);
+        }
+    }
+}
+
+impl From<Vec<PlotPoint>> for Trajectory {
+    fn from(points: Vec<PlotPoint>) -> Self {
+        Self {
+            points,
+            segment_starts: vec![],
+        }
+    }
+}
+
+/// how deep a segment of the line can be halved, which bounds the work around discontinuities
+const MAX_SUBDIVISION_DEPTH: usize = 20;
+/// entities can stick out of the board by their amplitude
+const OFF_BOARD_MARGIN: f64 = 10.0;
+/// segments out of the board are still halved a few times, in case the line comes back in between
+const MIN_OFF_BOARD_DEPTH: usize = 4;
+
+/// how a line y = f(x) is sampled
+pub struct LineSampling {
+    pub step: f64, // between the x of the grid the sampling starts from
+    pub max_segment_length: f64,
+    pub max_points: usize,
+    pub until_off_board: bool, // stop where the line leaves the board, as shots do
+}
+
+/// sample y = f(x) from `from_point` in `direction`, rotated by `angle` (in radians,
+/// counterclockwise) around `pivot`, in the order of the shot, until the line can't be on the
+/// board anymore (or until it leaves it, with `until_off_board`).
+/// x starts on a grid of `step`, relative to `from_point`. then segments are halved until they
+/// are shorter than `max_segment_length`, so steep parts get more points than flat ones (unless
+/// they are entirely on one side of the board, once rotated). the ones that stay long are discontinuities,
+/// as are undefined parts, and split the trajectory.
+/// the sampling stops when `max_points` is reached, which is reported by the returned boolean
 pub fn compute_line_points(
     math_expr: &MathExpression,
     from_point: &PlotPoint,
-    interval: (isize, isize),
+    board: &BoardConfig,
+    direction: ShotDirection,
+    sampling: &LineSampling,
+    pivot: &PlotPoint,
+    angle: f64,
+) -> (Trajectory, bool) {
+    let sample = |x: f64| {
+        let y = math_expr.compute(x).ok().filter(|y| y.is_finite())?;
+        let mut point = [PlotPoint::new(x + from_point.x, y + from_point.y)];
+        rotate_points(&mut point, pivot, angle);
+        Some(point[0])
+    };
+
+    // the rotation keeps distances to the pivot, and these are longer than x itself:
+    // past the farthest corner of the board, the line can't be on the board anymore
+    let ((x_min, x_max), (y_min, y_max)) = (board.x_bounds(), board.y_bounds());
+    let max_distance = [
+        (x_min, y_min),
+        (x_min, y_max),
+        (x_max, y_min),
+        (x_max, y_max),
+    ]
+    .into_iter()
+    .map(|(x, y)| distance(pivot, &PlotPoint::new(x, y)))
+    .fold(0.0, f64::max)
+        + (from_point.x - pivot.x).abs();
+    let grid_samples = (max_distance / sampling.step).ceil() as usize;
+    let mut trajectory = Trajectory::default();
+    let mut previous: Option<(f64, PlotPoint)> = None;
+    for i in 0..=grid_samples {
+        let x = direction.sign() * (i as f64 * sampling.step).min(max_distance);
+        let current = sample(x).map(|point| (x, point));
+        match (previous, current) {
+            (Some(previous), Some(current)) => {
+                let sampler = AdaptiveSampler {
+                    sample: &sample,
+                    x_bounds: (x_min - OFF_BOARD_MARGIN, x_max + OFF_BOARD_MARGIN),
+                    y_bounds: (y_min - OFF_BOARD_MARGIN, y_max + OFF_BOARD_MARGIN),
+                    sampling,
+                };
+                if !sampler.subdivide(previous, current, 0, &mut trajectory) {
+                    return (trajectory, true);
+                }
+            }
+            // the line starts again after an undefined part
+            (None, Some((_, point))) => {
+                trajectory.break_segment();
+                trajectory.push(point);
+            }
+            (_, None) => {}
+        }
+        // the shot ends where it leaves the board
+        if sampling.until_off_board && current.is_some_and(|(_, point)| !board.contains(&point)) {
+            break;
+        }
+        previous = current;
+    }
+    (trajectory, false)
+}
+
+struct AdaptiveSampler<'a, F: Fn(f64) -> Option<PlotPoint>> {
+    sample: &'a F,
+    // beyond them, segments can't touch anything. the points being rotated,
+    // a line can leave the board on any side
+    x_bounds: (f64, f64),
+    y_bounds: (f64, f64),
+    sampling: &'a LineSampling,
+}
+
+impl<F: Fn(f64) -> Option<PlotPoint>> AdaptiveSampler<'_, F> {
+    /// push the points after `from` up to `to` included, returns false when the budget is exhausted
+    fn subdivide(
+        &self,
+        from: (f64, PlotPoint),
+        to: (f64, PlotPoint),
+        depth: usize,
+        trajectory: &mut Trajectory,
+    ) -> bool {
+        let is_short = distance(&from.1, &to.1) <= self.sampling.max_segment_length;
+        let beyond = |from: f64, to: f64, (min, max): (f64, f64)| {
+            (from < min && to < min) || (from > max && to > max)
+        };
+        let is_off_board = depth >= MIN_OFF_BOARD_DEPTH
+            && (beyond(from.1.x, to.1.x, self.x_bounds) || beyond(from.1.y, to.1.y, self.y_bounds));
+        if !is_short && !is_off_board {
+            let middle_x = (from.0 + to.0) / 2.0;
+            match (self.sample)(middle_x) {
+                Some(middle) if depth < MAX_SUBDIVISION_DEPTH => {
+                    let middle = (middle_x, middle);
+                    return self.subdivide(from, middle, depth + 1, trajectory)
+                        && self.subdivide(middle, to, depth + 1, trajectory);
+                }
+                // a jump, a pole or an undefined part
+                _ => trajectory.break_segment(),
+            }
+        }
+        trajectory.push(to.1);
+        trajectory.points.len() < self.sampling.max_points
+    }
+}
+
+/// the euclidean distance between two points
+pub(crate) fn distance(a: &PlotPoint, b: &PlotPoint) -> f64 {
+    (b.x - a.x).hypot(b.y - a.y)
+}
+
+/// rotate the points by `angle` (in radians, counterclockwise) around `center`
+pub fn rotate_points(points: &mut [PlotPoint], center: &PlotPoint, angle: f64) {
+    let (sin, cos) = angle.sin_cos();
+    for point in points {
+        let (dx, dy) = (point.x - center.x, point.y - center.y);
+        point.x = center.x + dx * cos - dy * sin;
+        point.y = center.y + dx * sin + dy * cos;
+    }
+}
+
+/// sample the parametric curve (x(t), y(t)) for t in `t_range`, relatively to `from_point`
+pub fn compute_parametric_points(
+    (x_expr, y_expr): (&MathExpression, &MathExpression),
+    from_point: &PlotPoint,
+    t_range: (f64, f64),
+    resolution: usize,
+) -> Trajectory {
+    compute_curve_points(t_range, resolution, |t| {
+        let (x, y) = (x_expr.compute(t).ok()?, y_expr.compute(t).ok()?);
+        Some(PlotPoint::new(x + from_point.x, y + from_point.y))
+    })
+}
+
+/// sample the polar curve r(θ) for θ in `theta_range`, around `from_point`
+pub fn compute_polar_points(
+    math_expr: &MathExpression,
+    from_point: &PlotPoint,
+    theta_range: (f64, f64),
+    resolution: usize,
+) -> Trajectory {
+    compute_curve_points(theta_range, resolution, |theta| {
+        let r = math_expr.compute(theta).ok()?;
+        Some(PlotPoint::new(
+            from_point.x + r * theta.cos(),
+            from_point.y + r * theta.sin(),
+        ))
+    })
+}
+
+/// sample a curve for its parameter in `range`, the points where it is undefined split it
+fn compute_curve_points(
+    range: (f64, f64),
+    resolution: usize,
+    point_at: impl Fn(f64) -> Option<PlotPoint>,
+) -> Trajectory {
+    let samples = ((range.1 - range.0) * resolution as f64).floor() as usize;
+    let mut trajectory = Trajectory::default();
+    for i in 0..=samples {
+        let parameter = range.0 + i as f64 / resolution as f64;
+        match point_at(parameter).filter(|point| point.x.is_finite() && point.y.is_finite()) {
+            Some(point) => trajectory.push(point),
+            None => trajectory.break_segment(),
+        }
+    }
+    trajectory
+}
+
+/// order of a differential equation, the second one needs the initial slope y'(0)
+#[derive(Clone, Copy)]
+pub enum OdeOrder {
+    First,
+    Second { initial_slope: f64 },
+}
+
+/// integrate `y' = f(x, y)` or `y'' = f(x, y, y')` with the Runge-Kutta 4 method,
+/// starting at `from_point` with y(0) = 0, in `direction` until the board edge
+pub fn compute_ode_points(
+    math_expr: &MathExpression,
+    order: OdeOrder,
+    from_point: &PlotPoint,
+    board: &BoardConfig,
     resolution: usize,
-) -> PlotPoints {
-    ((-(from_point.x as isize - interval.0) * resolution as isize)
-        ..=((interval.1 - from_point.x as isize) * resolution as isize))
-        .filter_map(|i| {
-            let x = i as f64 * 1.0 / resolution as f64;
-            match math_expr.compute(x) {
-                Ok(y) => Some([x + from_point.x, y + from_point.y]),
-                Err(_) => None,
+    direction: ShotDirection,
+) -> Trajectory {
+    // the state is [y, y'], y' being unused for first order equations
+    let derivatives = |x: f64, [y, dy]: [f64; 2]| match order {
+        OdeOrder::First => Some([math_expr.compute_with(&[x, y]).ok()?, 0.0]),
+        OdeOrder::Second { .. } => Some([dy, math_expr.compute_with(&[x, y, dy]).ok()?]),
+    };
+    let initial_state = match order {
+        OdeOrder::First => [0.0, 0.0],
+        OdeOrder::Second { initial_slope } => [0.0, initial_slope],
+    };
+    let integrate = |to: f64, step: f64| {
+        let mut points = vec![];
+        let mut state = initial_state;
+        let steps = (to / step).ceil() as usize;
+        for i in 0..steps {
+            let x = i as f64 * step;
+            // the last step ends right on the board edge
+            let step = match i + 1 == steps {
+                true => to - x,
+                false => step,
+            };
+            match rk4_step(derivatives, x, state, step) {
+                Some(next_state) if next_state[0].is_finite() => state = next_state,
+                _ => break, // the solution is not defined further
             }
-        })
-        .collect()
+            points.push(PlotPoint::new(
+                x + step + from_point.x,
+                state[0] + from_point.y,
+            ));
+        }
+        points
+    };
+
+    let step = direction.sign() / resolution as f64;
+    let to = direction.sign() * direction.distance_to_edge(from_point, board);
+    let mut points = vec![*from_point];
+    points.extend(integrate(to, step));
+    Trajectory::from(points)
+}
+
+fn rk4_step(
+    derivatives: impl Fn(f64, [f64; 2]) -> Option<[f64; 2]>,
+    x: f64,
+    state: [f64; 2],
+    step: f64,
+) -> Option<[f64; 2]> {
+    let shifted = |k: [f64; 2], factor: f64| [state[0] + factor * k[0], state[1] + factor * k[1]];
+    let k1 = derivatives(x, state)?;
+    let k2 = derivatives(x + step / 2.0, shifted(k1, step / 2.0))?;
+    let k3 = derivatives(x + step / 2.0, shifted(k2, step / 2.0))?;
+    let k4 = derivatives(x + step, shifted(k3, step))?;
+    Some([0, 1].map(|i| state[i] + step / 6.0 * (k1[i] + 2.0 * k2[i] + 2.0 * k3[i] + k4[i])))
 }
 
 pub fn compute_polygon_points(n_gon: usize, amplitude: f64) -> PlotPoints {
@@ -34,39 +455,73 @@ pub fn compute_polygon_points(n_gon: usize, amplitude: f64) -> PlotPoints {
     )
 }
 
-pub fn get_app_plot() -> Plot {
+pub fn get_app_plot(board: &BoardConfig) -> Plot {
+    let ((x_min, x_max), (y_min, y_max)) = (board.x_bounds(), board.y_bounds());
     Plot::new("game_graph")
         // block all plot camera movements
         .allow_zoom(false)
         .allow_drag(false)
         .allow_boxed_zoom(false)
         .allow_scroll(false)
-        // block plot bounds to [-25, 25] in the x and y axes
-        .include_x(-25.0)
-        .include_x(25.0)
-        .include_y(-25.0)
-        .include_y(25.0)
+        // block plot bounds to the board, with units as long in the x and y axes
+        .include_x(x_min)
+        .include_x(x_max)
+        .include_y(y_min)
+        .include_y(y_max)
+        .data_aspect(1.0)
+        .view_aspect(board.aspect_ratio() as f32)
 }
 
+/// the color egui used to pick for the graph, now that it is drawn in several lines
+const GRAPH_COLOR: Color32 = Color32::from_rgb(188, 77, 77);
+
 pub trait Plotter {
-    fn render_graph(&mut self, points: &[PlotPoint], until_frame: usize);
+    fn render_graph(&mut self, trajectory: &Trajectory, until_frame: usize);
+    fn render_derivative(&mut self, trajectory: &Trajectory);
+    fn render_exit_point(&mut self, point: &PlotPoint);
     fn render_obstacles(&mut self, sprites: &[(Vec<PlotPoint>, PlotPoint, f64)]);
     fn render_player(&mut self, sprite: &[PlotPoint]);
     fn render_ennemies(&mut self, sprites: &[(Vec<PlotPoint>, PlotPoint)]);
 }
 
 impl Plotter for PlotUi {
-    fn render_graph(&mut self, points: &[PlotPoint], until_frame: usize) {
-        let until_frame = match until_frame > points.len() - 1 {
-            true => points.len() - 1,
-            false => until_frame,
-        };
+    fn render_graph(&mut self, trajectory: &Trajectory, until_frame: usize) {
+        // each segment is its own line, so that discontinuities are not bridged
+        let mut frames_left = until_frame + 1;
+        for segment in trajectory.segments() {
+            if frames_left == 0 {
+                break;
+            }
+            let drawn = segment.len().min(frames_left);
+            frames_left -= drawn;
 
-        let points: PlotPoints = points[..=until_frame]
-            .iter()
-            .map(|&PlotPoint { x, y }| [x, y])
-            .collect();
-        self.line(Line::new(points).width(2.0));
+            let points: PlotPoints = segment[..drawn]
+                .iter()
+                .map(|&PlotPoint { x, y }| [x, y])
+                .collect();
+            self.line(Line::new(points).color(GRAPH_COLOR).width(2.0));
+        }
+    }
+    fn render_derivative(&mut self, trajectory: &Trajectory) {
+        for segment in trajectory.segments() {
+            let points: PlotPoints = segment.iter().map(|&PlotPoint { x, y }| [x, y]).collect();
+            self.line(
+                Line::new(points)
+                    .width(1.5)
+                    .color(Color32::GOLD)
+                    .style(LineStyle::dashed_loose())
+                    .name("f'(x)"),
+            );
+        }
+    }
+    fn render_exit_point(&mut self, point: &PlotPoint) {
+        self.points(
+            Points::new([point.x, point.y])
+                .shape(MarkerShape::Cross)
+                .radius(6.0)
+                .color(GRAPH_COLOR)
+                .name("left the board"),
+        );
     }
     fn render_obstacles(&mut self, sprites: &[(Vec<PlotPoint>, PlotPoint, f64)]) {
         for sprite in sprites {
@@ -87,3 +542,177 @@ impl Plotter for PlotUi {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::eval::{FIRST_ORDER_ODE_VARIABLES, SECOND_ORDER_ODE_VARIABLES};
+
+    const SAMPLING: LineSampling = LineSampling {
+        step: 0.25,
+        max_segment_length: 0.25,
+        max_points: 20_000,
+        until_off_board: true,
+    };
+
+    fn shoot(raw_expr: &str, player: PlotPoint, angle_degrees: f64) -> Trajectory {
+        let math_expr = MathExpression::new(raw_expr).unwrap();
+        let (trajectory, cut_short) = compute_line_points(
+            &math_expr,
+            &player,
+            &BoardConfig::default(),
+            ShotDirection::facing_from(&player),
+            &SAMPLING,
+            &player,
+            angle_degrees.to_radians(),
+        );
+        assert!(!cut_short);
+        trajectory
+    }
+
+    fn point(x: f64) -> PlotPoint {
+        PlotPoint::new(x, 0.0)
+    }
+
+    /// 0 1 2 | 3 4 | 5
+    fn broken_trajectory() -> Trajectory {
+        let mut trajectory = Trajectory::default();
+        for x in 0..6 {
+            if x == 3 || x == 5 {
+                trajectory.break_segment();
+            }
+            trajectory.push(point(x as f64));
+        }
+        trajectory
+    }
+
+    #[test]
+    fn trajectory_links() {
+        let links: Vec<_> = broken_trajectory()
+            .links()
+            .map(|(id, from, to)| (id, from.x, to.x))
+            .collect();
+        // no link across the breaks, and the lonely point is linked to itself
+        assert_eq!(
+            links,
+            [(0, 0.0, 1.0), (1, 1.0, 2.0), (3, 3.0, 4.0), (5, 5.0, 5.0)]
+        );
+        assert_eq!(broken_trajectory().segments().count(), 3);
+    }
+
+    #[test]
+    fn trajectory_cut_at() {
+        let mut trajectory = broken_trajectory();
+        trajectory.cut_at(3, point(3.5));
+        let xs: Vec<_> = trajectory.points.iter().map(|point| point.x).collect();
+        assert_eq!(xs, [0.0, 1.0, 2.0, 3.0, 3.5]);
+        assert_eq!(trajectory.segments().count(), 2);
+    }
+
+    #[test]
+    fn trajectory_keep_first_segment() {
+        let mut trajectory = broken_trajectory();
+        trajectory.keep_first_segment();
+        assert_eq!(trajectory.points.len(), 3);
+        assert_eq!(trajectory.segments().count(), 1);
+
+        let mut continuous = Trajectory::from(vec![point(0.0), point(1.0)]);
+        continuous.keep_first_segment();
+        assert_eq!(continuous.points.len(), 2);
+    }
+
+    #[test]
+    fn angled_shots_reach_the_board_edge() {
+        let trajectory = shoot("sqrt(x-10)", PlotPoint::new(-1.0, -20.0), 90.0);
+        let last = trajectory.points.last().unwrap();
+        assert!(!BoardConfig::default().contains(last));
+        assert!(last.y > 24.0);
+    }
+
+    #[test]
+    fn rotated_shots_are_finely_sampled_on_the_board() {
+        let board = BoardConfig::default();
+        let trajectory = shoot("300sin(x/2)", PlotPoint::new(20.0, 20.0), 90.0);
+        for (_, from, to) in trajectory.links() {
+            if board.contains(&from) || board.contains(&to) {
+                assert!(distance(&from, &to) <= SAMPLING.max_segment_length);
+            }
+        }
+    }
+
+    #[test]
+    fn shots_stop_once_off_the_board() {
+        // y = x leaves the classic board through its top at x = 5
+        let trajectory = shoot("x", PlotPoint::new(-20.0, 0.0), 0.0);
+        let last = trajectory.points.last().unwrap();
+        assert!(!BoardConfig::default().contains(last));
+        assert!(last.x <= 5.0 + SAMPLING.step);
+    }
+
+    fn integrate(raw_expr: &str, order: OdeOrder, from_point: PlotPoint) -> Trajectory {
+        let variables = match order {
+            OdeOrder::First => FIRST_ORDER_ODE_VARIABLES,
+            OdeOrder::Second { .. } => SECOND_ORDER_ODE_VARIABLES,
+        };
+        let math_expr = MathExpression::with_variables(raw_expr, variables).unwrap();
+        compute_ode_points(
+            &math_expr,
+            order,
+            &from_point,
+            &BoardConfig::default(),
+            100,
+            ShotDirection::facing_from(&from_point),
+        )
+    }
+
+    #[test]
+    fn first_order_ode() {
+        let trajectory = integrate("1", OdeOrder::First, point(-10.0));
+        assert_eq!(trajectory.points[0], point(-10.0));
+        for point in &trajectory.points {
+            assert!((point.y - (point.x + 10.0)).abs() < 1e-9, "{point:?}");
+        }
+    }
+
+    #[test]
+    fn second_order_ode() {
+        let from = PlotPoint::new(-20.0, 0.0);
+        let trajectory = integrate("-y", OdeOrder::Second { initial_slope: 1.0 }, from);
+        for point in &trajectory.points {
+            let expected = (point.x - from.x).sin();
+            assert!((point.y - expected).abs() < 1e-6, "{point:?}");
+        }
+        let quarter = trajectory
+            .points
+            .iter()
+            .min_by(|a, b| {
+                let from_quarter = |point: &&PlotPoint| (point.x - from.x - PI / 2.0).abs();
+                from_quarter(a).total_cmp(&from_quarter(b))
+            })
+            .unwrap();
+        assert!((quarter.y - 1.0).abs() < 1e-3);
+    }
+
+    #[test]
+    fn left_facing_ode() {
+        let trajectory = integrate("1", OdeOrder::First, point(10.0));
+        let xs = trajectory.points.iter().map(|point| point.x);
+        assert!(xs.clone().zip(xs.skip(1)).all(|(x, next_x)| next_x < x));
+        let last = trajectory.points.last().unwrap();
+        assert!((last.x + 25.0).abs() < 1e-9);
+        assert!((last.y + 35.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn ode_last_step_ends_on_the_board_edge() {
+        // 25.305 to the left edge is not a multiple of the 0.01 step
+        let trajectory = integrate("x", OdeOrder::First, point(0.305));
+        let last = trajectory.points.last().unwrap();
+        let length = 25.305;
+        assert!((last.x + 25.0).abs() < 1e-9);
+        assert!((last.y - length * length / 2.0).abs() < 1e-6);
+        // a full step would have gone past the edge
+        let before_last = trajectory.points[trajectory.points.len() - 2];
+        assert!(before_last.x > -25.0 && before_last.x - last.x < 0.01 + 1e-9);
+    }
+}
@@ -1,6 +1,11 @@
-use std::time::{Duration, Instant};
+use std::{
+    ops::Range,
+    time::{Duration, Instant},
+};
 
-use egui::{Color32, FontId, RichText};
+use egui::{text::LayoutJob, Color32, FontId, Grid, RichText, ScrollArea, TextFormat, Ui};
+
+use crate::eval::{CONSTANTS, FUNCTIONS};
 
 #[allow(dead_code)]
 #[derive(Clone, Copy)]
@@ -24,6 +29,62 @@ pub fn rich_text(text: &str, text_type: UITypes) -> RichText {
         .font(FontId::monospace(16.0))
 }
 
+/// lay out a text input, with the chars in `highlight` (if any) marked as erroneous
+pub fn highlighted_layout_job(
+    text: &str,
+    highlight: Option<&Range<usize>>,
+    font_id: FontId,
+) -> LayoutJob {
+    let normal = TextFormat {
+        font_id: font_id.clone(),
+        color: Color32::WHITE,
+        ..Default::default()
+    };
+    let erroneous = TextFormat {
+        font_id,
+        color: Color32::WHITE,
+        background: Color32::from_rgb(150, 30, 30),
+        ..Default::default()
+    };
+
+    // the highlight is expressed in chars, the layout job in bytes
+    let byte_offset = |char_idx: usize| {
+        text.char_indices()
+            .nth(char_idx)
+            .map_or(text.len(), |(byte_idx, _)| byte_idx)
+    };
+    let (start, end) = highlight.map_or((text.len(), text.len()), |span| {
+        (byte_offset(span.start), byte_offset(span.end))
+    });
+
+    let mut layout_job = LayoutJob::default();
+    layout_job.append(&text[..start], 0.0, normal.clone());
+    layout_job.append(&text[start..end], 0.0, erroneous);
+    layout_job.append(&text[end..], 0.0, normal);
+    layout_job
+}
+
+/// list every function and constant that can be typed in an equation
+pub fn render_functions_help(ui: &mut Ui) {
+    ScrollArea::vertical().show(ui, |ui| {
+        Grid::new("functions_help")
+            .striped(true)
+            .num_columns(2)
+            .show(ui, |ui| {
+                for function in &FUNCTIONS {
+                    ui.label(rich_text(function.signature, UITypes::Info));
+                    ui.label(function.description);
+                    ui.end_row();
+                }
+                for (name, value) in CONSTANTS {
+                    ui.label(rich_text(name, UITypes::Success));
+                    ui.label(format!("constant, {value:.5}..."));
+                    ui.end_row();
+                }
+            });
+    });
+}
+
 pub struct Message {
     content: String,
     duration: Duration,